@@ -0,0 +1,51 @@
+//! Compares `predict_completions_n`, which allocates a `String` per
+//! completion, against `predict_completions_ref`, which borrows them.
+
+mod common;
+#[path = "common/counting.rs"]
+mod counting;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rustocompleter::Autocompleter;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+#[global_allocator]
+static ALLOCATOR: counting::Counting = counting::Counting;
+
+/// Number of calls `cost_of` averages its timing over.
+const CALLS: u32 = 1000;
+
+/// Counts the allocations one call of `f` makes, and times it over `CALLS` calls.
+fn cost_of<R>(f: impl Fn() -> R) -> (usize, Duration) {
+    let before = counting::allocations();
+    let result = f();
+    let made = counting::allocations() - before;
+    drop(result);
+
+    let start = Instant::now();
+    for _ in 0..CALLS {
+        black_box(f());
+    }
+    (made, start.elapsed() / CALLS)
+}
+
+fn borrowed(c: &mut Criterion) {
+    let ac = common::dictionary(Autocompleter::builder());
+    let (owned_allocs, owned_time) = cost_of(|| ac.predict_completions_n(black_box("s"), 10));
+    let (borrowed_allocs, borrowed_time) =
+        cost_of(|| ac.predict_completions_ref(black_box("s"), 10));
+    println!("per call: owned {owned_allocs} allocations in {owned_time:?}, borrowed {borrowed_allocs} allocations in {borrowed_time:?}");
+
+    let mut group = c.benchmark_group("10 completions of \"s\"");
+    group.bench_function("owned", |b| {
+        b.iter(|| ac.predict_completions_n(black_box("s"), 10))
+    });
+    group.bench_function("borrowed", |b| {
+        b.iter(|| ac.predict_completions_ref(black_box("s"), 10))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, borrowed);
+criterion_main!(benches);
@@ -0,0 +1,40 @@
+//! Compares loading a large dictionary into a completer sized up front with
+//! `with_capacity` or `reserve` against one that isn't.
+
+mod common;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rustocompleter::Autocompleter;
+
+fn bulk_load(c: &mut Criterion) {
+    let words = common::words();
+    let mut group = c.benchmark_group("load 200k words");
+    // Each load takes a while, so fewer samples still give a stable figure
+    group.sample_size(10);
+    group.bench_function("no hint", |b| {
+        b.iter(|| {
+            let mut ac = Autocompleter::new();
+            common::fill(&mut ac, &words);
+            ac
+        })
+    });
+    group.bench_function("with_capacity", |b| {
+        b.iter(|| {
+            let mut ac = Autocompleter::with_capacity(words.len());
+            common::fill(&mut ac, &words);
+            ac
+        })
+    });
+    group.bench_function("reserve", |b| {
+        b.iter(|| {
+            let mut ac = Autocompleter::builder().build();
+            ac.reserve(words.len());
+            common::fill(&mut ac, &words);
+            ac
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bulk_load);
+criterion_main!(benches);
@@ -0,0 +1,60 @@
+//! Shared setup for the benchmarks.
+
+use rustocompleter::{Autocompleter, AutocompleterBuilder};
+
+/// Number of distinct words in the benchmark dictionary.
+pub const WORDS: usize = 200_000;
+
+/// Makes up the words of the benchmark dictionary, the same on every run,
+/// with roughly Zipf-distributed counts so a few words are far more popular
+/// than the rest, like in real text.
+///
+/// # Return value
+///
+/// `WORDS` `(word, count)` pairs. A word can come up more than once.
+pub fn words() -> Vec<(String, i32)> {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    (0..WORDS)
+        .map(|i| {
+            // Small xorshift generator, so the words don't depend on `rand`
+            let mut word = String::new();
+            let len = 3 + i % 8;
+            while word.len() < len {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                word.push(char::from(b'a' + (state % 26) as u8));
+            }
+            (word, (WORDS / (i + 1)) as i32 + 1)
+        })
+        .collect()
+}
+
+/// Adds every word of `words` to an `Autocompleter`.
+///
+/// # Arguments
+///
+/// `ac` (`&mut Autocompleter`) - Completer to fill.
+///
+/// `words` (`&[(String, i32)]`) - Words and their counts, from `words`.
+pub fn fill(ac: &mut Autocompleter, words: &[(String, i32)]) {
+    for (word, count) in words {
+        ac.add_word_with_count(word.clone(), *count);
+    }
+}
+
+/// Builds the benchmark dictionary from `words`.
+///
+/// # Arguments
+///
+/// `builder` (`AutocompleterBuilder`) - Configuration to build the dictionary with.
+///
+/// # Return value
+///
+/// The filled `Autocompleter`.
+#[allow(dead_code)]
+pub fn dictionary(builder: AutocompleterBuilder) -> Autocompleter {
+    let mut ac = builder.build();
+    fill(&mut ac, &words());
+    ac
+}
@@ -0,0 +1,26 @@
+//! Compares the heap-based `most_popular` against sorting every word of
+//! the dictionary to take the top 10.
+
+mod common;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rustocompleter::Autocompleter;
+use std::hint::black_box;
+
+fn most_popular(c: &mut Criterion) {
+    let ac = common::dictionary(Autocompleter::builder());
+    let mut group = c.benchmark_group("10 most popular words");
+    group.bench_function("heap", |b| b.iter(|| ac.most_popular(black_box(10))));
+    group.bench_function("full sort", |b| {
+        b.iter(|| {
+            let mut words = ac.to_vec();
+            words.sort_by(|(a, x), (b, y)| y.cmp(x).then_with(|| a.cmp(b)));
+            words.truncate(black_box(10));
+            words
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, most_popular);
+criterion_main!(benches);
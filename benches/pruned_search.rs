@@ -0,0 +1,27 @@
+//! Compares the top 10 completions of a short prefix found with
+//! `max_subtree_rank` pruning against collecting every completion first.
+
+mod common;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rustocompleter::Autocompleter;
+use std::hint::black_box;
+
+fn top_ten(c: &mut Criterion) {
+    let ac = common::dictionary(Autocompleter::builder());
+    let mut group = c.benchmark_group("top 10 of \"s\"");
+    group.bench_function("pruned", |b| {
+        b.iter(|| ac.predict_completions_with_counts(black_box("s"), 10))
+    });
+    group.bench_function("full search", |b| {
+        b.iter(|| {
+            ac.predict_ranked_iter(black_box("s"))
+                .take(10)
+                .collect::<Vec<_>>()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, top_ten);
+criterion_main!(benches);
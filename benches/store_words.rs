@@ -0,0 +1,47 @@
+//! Compares the memory a dictionary takes and how fast it predicts with
+//! words stored in their nodes and with `store_words(false)`. Run it again
+//! with `--no-default-features` to see the nodes without a `data` field.
+
+mod common;
+#[path = "common/counting.rs"]
+mod counting;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rustocompleter::{Autocompleter, AutocompleterBuilder};
+use std::hint::black_box;
+
+#[global_allocator]
+static ALLOCATOR: counting::Counting = counting::Counting;
+
+/// Builds the benchmark dictionary, returning it and the bytes it holds.
+fn measured(builder: AutocompleterBuilder) -> (Autocompleter, usize) {
+    let before = counting::live_bytes();
+    let ac = common::dictionary(builder);
+    (ac, counting::live_bytes() - before)
+}
+
+fn store_words(c: &mut Criterion) {
+    let (stored, stored_bytes) = measured(Autocompleter::builder());
+    let (rebuilt, rebuilt_bytes) = measured(Autocompleter::builder().store_words(false));
+    let feature = if cfg!(feature = "store-words") {
+        "on"
+    } else {
+        "off"
+    };
+    println!(
+        "memory for {} words, store-words {feature}: stored {stored_bytes} bytes, not stored {rebuilt_bytes} bytes",
+        common::WORDS,
+    );
+
+    let mut group = c.benchmark_group("10 completions of \"s\"");
+    group.bench_function("stored", |b| {
+        b.iter(|| stored.predict_completions_n(black_box("s"), 10))
+    });
+    group.bench_function("not stored", |b| {
+        b.iter(|| rebuilt.predict_completions_n(black_box("s"), 10))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, store_words);
+criterion_main!(benches);
@@ -1,183 +1,5367 @@
 extern crate fs_err;
 use fs_err::File;
 
-use std::io::{BufRead, BufReader};
+#[cfg(feature = "gzip")]
+use flate2::read::GzDecoder;
+#[cfg(feature = "rand")]
+use rand::{Rng, RngExt};
+#[cfg(feature = "regex")]
+use regex::Regex;
+#[cfg(feature = "stemming")]
+use rust_stemmers::{Algorithm, Stemmer};
+#[cfg(feature = "unicode-segmentation")]
+use unicode_segmentation::GraphemeCursor;
 
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::fmt::Display;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+mod builder;
+mod fuzzy;
 mod mwt;
-use mwt::{Mwt, MwtNode};
+mod session;
+mod shared;
+pub use builder::AutocompleterBuilder;
+use mwt::Mwt;
+pub use mwt::{IntoIter, MwtNode, RootMut};
+pub use session::Session;
+pub use shared::SharedAutocompleter;
 
+// Default minimum number of characters (not bytes) a prefix needs before completions are offered
 const MIN_LEN: usize = 1;
-const ELEMENTS_TO_RETURN: usize = 10;
+// Largest edit distance `suggest_correction` will look for a replacement within
+const MAX_CORRECTION_DISTANCE: usize = 2;
+/// Number of completions `predict_completions` returns.
+pub const ELEMENTS_TO_RETURN: usize = 10;
+const GZIP_EXTENSION: &str = ".gz";
+// Byte order mark that may start a UTF-8 text file
+const BOM: char = '\u{FEFF}';
+// Number of bytes read between calls to a loading progress callback
+const PROGRESS_INTERVAL: u64 = 64 * 1024;
 
 /// This struct contains functionality related to performing
 /// word autocompletion. It acts as a sort of wrapper class
 /// for the underlying MWT.
 ///
+/// Every word can carry a payload of type `T`, attached with
+/// `add_word_with_payload`. `T` is `()` unless the `Autocompleter` was built
+/// with `AutocompleterBuilder::build_with_payloads`. The constructors such as
+/// `new` and `from_file` always make an `Autocompleter<()>`; for another
+/// payload type, build an empty one and load it with `add_from_file`.
+///
+/// # Fields
+///
+/// `trie` (`Mwt<T>`) - The underlying MWT structure that provides the functionality.
+///
+/// `settings` (`Settings`) - Behavioural options chosen through `AutocompleterBuilder`.
+///
+/// `loaded_files` (`HashSet<PathBuf>`) - Canonical paths of every file loaded without errors
+/// by `add_from_file` and the other file loaders since the last `clear`.
+///
+/// `log` (`WordLog`) - Log that added words are appended to, if opened with `open_with_log`.
+
+#[derive(Clone)]
+pub struct Autocompleter<T = ()> {
+    trie: Mwt<T>,
+    settings: Settings,
+    loaded_files: HashSet<PathBuf>,
+    log: WordLog,
+}
+
+/// Controls how `predict_completions` orders the completions it finds.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Most frequent words first, with alphabetical order breaking ties.
+    #[default]
+    FrequencyThenAlpha,
+    /// Alphabetical order first, with frequency breaking ties.
+    AlphaThenFrequency,
+    /// Strictly alphabetical order, ignoring frequency entirely.
+    AlphaOnly,
+}
+
+/// Controls how `SortOrder::FrequencyThenAlpha` orders completions that have
+/// the same frequency.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TieBreak {
+    /// Alphabetical order.
+    #[default]
+    Alphabetical,
+    /// Shorter words first, then alphabetical order.
+    ShorterFirst,
+    /// Longer words first, then alphabetical order.
+    LongerFirst,
+}
+
+/// Controls what happens when a new word arrives at an `Autocompleter` that
+/// has reached its `max_words` cap.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Reject the new word, leaving the stored words untouched.
+    #[default]
+    RejectNew,
+    /// Remove the word used least recently to make room for the new one.
+    /// A word counts as used when it's added again in any way, including
+    /// `add_word_with_count`, `set_rank` and `rename`, and when a prediction
+    /// returns it.
+    LeastRecentlyUsed,
+}
+
+/// Reasons `predict_completions_result` can come back without completions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PredictError {
+    /// The prefix has fewer characters than the `min_len` needed before
+    /// completions are offered.
+    PrefixTooShort { min_len: usize },
+    /// No stored word completes the prefix.
+    NoMatches,
+}
+
+impl std::fmt::Display for PredictError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PredictError::PrefixTooShort { min_len } => {
+                write!(f, "prefix is too short, type at least {min_len} characters")
+            }
+            PredictError::NoMatches => f.write_str("no stored word completes the prefix"),
+        }
+    }
+}
+
+impl std::error::Error for PredictError {}
+
+/// Caller-supplied scoring function set with `Autocompleter::set_ranker`.
+///
+/// Wrapped so `Settings` can still be printed and cloned.
+#[derive(Clone)]
+struct Ranker(Arc<RankFn>);
+
+/// Signature of a ranker: scores a word from the word itself and its rank.
+type RankFn = dyn Fn(&str, i32) -> f64 + Send + Sync;
+
+impl std::fmt::Debug for Ranker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Ranker(..)")
+    }
+}
+
+/// Append-only log of the words added since a dictionary was loaded, kept
+/// by an `Autocompleter` opened with `open_with_log`.
+///
+/// # Fields
+///
+/// `dict_path` (`String`) - Dictionary file that `compact_log` folds the log into.
+///
+/// `log_path` (`String`) - Path of the log file.
+///
+/// `file` (`Option<File>`) - The open log file, or `None` if no log is kept.
+///
+/// `error` (`Option<String>`) - First error hit while appending to the log, reported by
+/// `compact_log`.
+#[derive(Default)]
+struct WordLog {
+    dict_path: String,
+    log_path: String,
+    file: Option<File>,
+    error: Option<String>,
+}
+
+impl WordLog {
+    /// Appends a word to the log as a line of its own and syncs it to disk
+    /// before returning. Does nothing if no log is kept.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`&str`) - Word to append.
+    fn append(&mut self, word: &str) {
+        let Some(file) = &mut self.file else {
+            return;
+        };
+        let written = writeln!(file, "{word}").and_then(|()| file.sync_data());
+        if let Err(e) = written {
+            self.error
+                .get_or_insert_with(|| format!("Error writing to log `{}`: {e}", self.log_path));
+        }
+    }
+
+    /// Appends words like `append`, each on as many lines as it has uses, and
+    /// syncs them to disk once at the end. Does nothing if no log is kept.
+    ///
+    /// # Arguments
+    ///
+    /// `words` (`&[(Cow<str>, i32)]`) - Words to append, with their number of uses.
+    fn append_counted(&mut self, words: &[(Cow<'_, str>, i32)]) {
+        let Some(file) = &mut self.file else {
+            return;
+        };
+        let mut written = Ok(());
+        for (word, uses) in words {
+            for _ in 0..*uses {
+                written = written.and_then(|()| writeln!(file, "{word}"));
+            }
+        }
+        if let Err(e) = written.and_then(|()| file.sync_data()) {
+            self.error
+                .get_or_insert_with(|| format!("Error writing to log `{}`: {e}", self.log_path));
+        }
+    }
+}
+
+// A cloned `Autocompleter` doesn't keep a log, so only the original appends to the file
+impl Clone for WordLog {
+    fn clone(&self) -> WordLog {
+        WordLog::default()
+    }
+}
+
+/// Internal collection of the options an `Autocompleter` was built with.
+///
 /// # Fields
 ///
-/// `trie` (`Mwt`) - The underlying MWT structure that provides the functionality.
-pub struct Autocompleter {
-    trie: Mwt,
+/// `sort_order` (`SortOrder`) - Ordering applied to prediction results.
+///
+/// `tie_break` (`TieBreak`) - Ordering applied to results with equal frequency.
+///
+/// `recency_decay` (`Option<f64>`) - If set, words are ranked by a time-decayed score
+/// instead of their raw frequency, with this factor applied per insertion.
+///
+/// `stem` (`bool`) - Whether words are reduced to their English Porter stem before insertion.
+///
+/// `max_words` (`Option<usize>`) - Cap on the number of distinct words stored.
+///
+/// `eviction` (`EvictionPolicy`) - What happens to new words once `max_words` is reached.
+///
+/// `exclude_exact_match` (`bool`) - Whether a prefix that is itself a stored word is left
+/// out of its own completions.
+///
+/// `boost_exact_match` (`bool`) - Whether a prefix that is itself a stored word comes first
+/// in its own completions.
+///
+/// `keep_chars` (`HashSet<char>`) - ASCII punctuation characters that aren't trimmed from
+/// the end of words read from files.
+///
+/// `split_on_digits` (`bool`) - Whether digits split words read from files.
+///
+/// `min_prefix_len` (`Option<usize>`) - Characters a prefix needs before completions are
+/// offered, if different from `MIN_LEN`.
+///
+/// `sorted_traversal` (`bool`) - Whether searches visit children in character order.
+///
+/// `case_insensitive` (`bool`) - Whether words and queries are lowercased before use.
+///
+/// `preserve_case` (`bool`) - Whether words and queries are lowercased before use while
+/// predictions keep the casing each word was added with most often.
+///
+/// `normalize_whitespace` (`bool`) - Whether runs of whitespace in words and queries are
+/// collapsed to a single space before use.
+///
+/// `ignore_punctuation` (`bool`) - Whether ASCII punctuation and whitespace are stripped from
+/// words and queries before use.
+///
+/// `dedup_folded` (`bool`) - Whether completions that only differ in case are merged into one,
+/// shown in their most frequent spelling.
+///
+/// `track_sources` (`bool`) - Whether `add_word_with_source` records source identifiers.
+///
+/// `track_doc_freq` (`bool`) - Whether file loaders count the distinct lines each word
+/// appears on.
+///
+/// `float_scores` (`bool`) - Whether words are ranked by their float score instead of their
+/// frequency.
+///
+/// `store_words` (`Option<bool>`) - Whether word nodes keep a copy of their word, if different
+/// from the default of `true`.
+///
+/// `graphemes` (`bool`) - Whether completions that would split a grapheme cluster at the end
+/// of the prefix are left out.
+///
+/// `max_word_len` (`Option<usize>`) - Longest word, in characters, that will be stored.
+///
+/// `max_dfs_nodes` (`Option<usize>`) - Most MWT nodes a single prediction search visits.
+///
+/// `ranker` (`Option<Ranker>`) - If set, scores results in place of the `SortOrder`.
+#[derive(Debug, Default, Clone)]
+struct Settings {
+    sort_order: SortOrder,
+    tie_break: TieBreak,
+    recency_decay: Option<f64>,
+    #[cfg(feature = "stemming")]
+    stem: bool,
+    max_words: Option<usize>,
+    eviction: EvictionPolicy,
+    exclude_exact_match: bool,
+    boost_exact_match: bool,
+    keep_chars: HashSet<char>,
+    split_on_digits: bool,
+    min_prefix_len: Option<usize>,
+    sorted_traversal: bool,
+    case_insensitive: bool,
+    preserve_case: bool,
+    normalize_whitespace: bool,
+    ignore_punctuation: bool,
+    dedup_folded: bool,
+    track_sources: bool,
+    track_doc_freq: bool,
+    float_scores: bool,
+    store_words: Option<bool>,
+    #[cfg(feature = "unicode-segmentation")]
+    graphemes: bool,
+    max_word_len: Option<usize>,
+    max_dfs_nodes: Option<usize>,
+    ranker: Option<Ranker>,
 }
 
-/// This internal struct is used to store the results from the DFS
+/// A word together with its rank, as found by a search of the MWT.
 /// It's functionally identical to a tuple of `(count, data)`, just with the
 /// added benefit of being able to reference fields by name instead of by index.
 ///
+/// Ordering follows the canonical ranking: higher `count` first, then `data`
+/// alphabetically. Sorting a `Vec<ScoredWord>` therefore puts the most popular
+/// words first, the same way the default `SortOrder` does. Equality compares
+/// the same two fields.
+///
 /// # Fields
 ///
 /// `count` (`i32`) - number of instances of a particular word
 ///
 /// `data` (`String`) - the word itself
-struct SortResult {
-    count: i32,
-    data: String,
+///
+/// `last_seen` (`u64`) - insertion clock value when the word was last inserted
+///
+/// `recency` (`f64`) - time-decayed insertion count of the word
+///
+/// `score` (`f64`) - float score of the word, 0 unless it was loaded with one
+#[derive(Debug, Clone)]
+pub struct ScoredWord {
+    pub count: i32,
+    pub data: String,
+    last_seen: u64,
+    recency: f64,
+    pub score: f64,
+}
+
+/// Completions of a prefix together with context about them, as returned by
+/// `predict_completions_info`.
+///
+/// # Fields
+///
+/// `prefix` (`String`) - the prefix that was searched for, after case folding and
+/// whitespace normalization
+///
+/// `results` (`Vec<String>`) - the completions, in prediction order
+///
+/// `found` (`bool`) - whether any stored word begins with the prefix
+///
+/// `is_truncated` (`bool`) - whether more completions exist than were returned
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PredictionResult {
+    pub prefix: String,
+    pub results: Vec<String>,
+    pub found: bool,
+    pub is_truncated: bool,
+}
+
+/// Summary figures describing the contents of an `Autocompleter`.
+///
+/// # Fields
+///
+/// `words` (`usize`) - number of distinct words stored
+///
+/// `occurrences` (`u64`) - total number of times words were inserted, i.e. the sum of all ranks
+///
+/// `nodes` (`usize`) - number of nodes in the underlying MWT, including the root
+///
+/// `max_word_len` (`usize`) - length, in characters, of the longest stored word
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    pub words: usize,
+    pub occurrences: u64,
+    pub nodes: usize,
+    pub max_word_len: usize,
+}
+
+/// A prediction result that borrows its word from the MWT instead of owning
+/// a copy, used by `predict_completions_ref`.
+///
+/// # Fields
+///
+/// `node` (`&MwtNode`) - node holding the word
+///
+/// `word` (`Cow<str>`) - the word, borrowed from the node unless words aren't stored
+///
+/// `recency` (`f64`) - the node's time-decayed insertion count, adjusted by `sort_results`
+struct BorrowedWord<'a, T> {
+    node: &'a MwtNode<T>,
+    word: Cow<'a, str>,
+    recency: f64,
+}
+
+/// Access to what `sort_results` needs to rank a prediction result, so owned
+/// and borrowed results can be sorted the same way.
+trait Ranked {
+    /// The word itself.
+    fn word(&self) -> &str;
+    /// How many times the word was inserted.
+    fn count(&self) -> i32;
+    /// Insertion clock value when the word was last inserted.
+    fn last_seen(&self) -> u64;
+    /// Time-decayed insertion count of the word.
+    fn recency(&self) -> f64;
+    /// Replaces the time-decayed insertion count, once it's been decayed.
+    fn set_recency(&mut self, recency: f64);
+    /// Float score of the word.
+    fn score(&self) -> f64;
+}
+
+/// A prediction result that can be made from the node its word was found
+/// at, so the searches behind `top_candidates` collect owned and borrowed
+/// results alike. Results order best first, like `ScoredWord`.
+trait Found<'a, T>: Ranked + Ord {
+    /// Makes a result for the word held at `node`.
+    ///
+    /// # Arguments
+    ///
+    /// `node` (`&MwtNode`) - Node holding the word.
+    ///
+    /// `path` (`&str`) - Characters leading from the root to `node`.
+    fn found(node: &'a MwtNode<T>, path: &str) -> Self;
+}
+
+impl ScoredWord {
+    fn from_node<T>(node: &MwtNode<T>, word: &str) -> ScoredWord {
+        ScoredWord {
+            count: node.get_rank(),
+            data: word.to_string(),
+            last_seen: node.get_last_seen(),
+            recency: node.get_recency(),
+            score: node.get_score(),
+        }
+    }
+}
+
+impl<T> Found<'_, T> for ScoredWord {
+    fn found(node: &MwtNode<T>, path: &str) -> ScoredWord {
+        ScoredWord::from_node(node, path)
+    }
+}
+
+impl<'a, T> Found<'a, T> for BorrowedWord<'a, T> {
+    fn found(node: &'a MwtNode<T>, path: &str) -> BorrowedWord<'a, T> {
+        BorrowedWord {
+            node,
+            word: Autocompleter::word_of(node, path),
+            recency: node.get_recency(),
+        }
+    }
+}
+
+impl Ranked for ScoredWord {
+    fn word(&self) -> &str {
+        &self.data
+    }
+
+    fn count(&self) -> i32 {
+        self.count
+    }
+
+    fn last_seen(&self) -> u64 {
+        self.last_seen
+    }
+
+    fn recency(&self) -> f64 {
+        self.recency
+    }
+
+    fn set_recency(&mut self, recency: f64) {
+        self.recency = recency;
+    }
+
+    fn score(&self) -> f64 {
+        self.score
+    }
 }
 
-impl SortResult {
-    fn new(count: i32, data: String) -> SortResult {
-        SortResult { count, data }
+impl<T> Ranked for BorrowedWord<'_, T> {
+    fn word(&self) -> &str {
+        &self.word
+    }
+
+    fn count(&self) -> i32 {
+        self.node.get_rank()
+    }
+
+    fn last_seen(&self) -> u64 {
+        self.node.get_last_seen()
+    }
+
+    fn recency(&self) -> f64 {
+        self.recency
+    }
+
+    fn set_recency(&mut self, recency: f64) {
+        self.recency = recency;
+    }
+
+    fn score(&self) -> f64 {
+        self.node.get_score()
     }
 }
 
 impl Autocompleter {
-    /// Constructs a new, empty `Autocompleter`.
+    /// Constructs a new, empty `Autocompleter` with the default settings.
     pub fn new() -> Autocompleter {
-        Autocompleter { trie: Mwt::new() }
+        Autocompleter::builder().build()
+    }
+
+    /// Returns an `AutocompleterBuilder` for constructing an `Autocompleter`
+    /// with non-default settings.
+    pub fn builder() -> AutocompleterBuilder {
+        AutocompleterBuilder::new()
     }
 
     /// Constructs a new `Autocompleter` and fills it in with the values
     /// from a given file.
     ///
+    /// See `add_from_file` for how the file is read.
+    ///
     /// # Arguments
     ///
-    /// `dict_filename` (`&String`) - Name of the file to parse for the dictionary.
+    /// `dict_filename` (`&str`) - Name of the file to parse for the dictionary.
     ///
     /// # Return value
     ///
     /// Either the constructed `Autocompleter`, or a `Error` with the error string.
-    pub fn from_file(dict_filename: &String) -> Result<Autocompleter, String> {
+    pub fn from_file(dict_filename: &str) -> Result<Autocompleter, String> {
         let mut val = Autocompleter::new();
+        val.add_from_file(dict_filename)?;
+        Ok(val)
+    }
 
-        // Try to open the file for reading, or bail out if an error occurs.
-        let dict_file = match File::open(dict_filename) {
+    /// Constructs a new `Autocompleter` from a dictionary file like
+    /// `from_file`, then replays a log of words added in earlier sessions on
+    /// top of it. From then on, every word passed to `add_word`,
+    /// `add_word_counted` or `add_words`, or merged in by `merge_from_file`,
+    /// is appended to the log and synced to disk before the call returns, so
+    /// no added word is lost if the program crashes before the dictionary is
+    /// saved. `compact_log` folds the log into the dictionary file.
+    ///
+    /// The log is in the same format as a dictionary file and is read back
+    /// the same way, so a logged word is tokenized on replay exactly as it
+    /// would be after being folded into the dictionary. The log file is
+    /// created if it doesn't exist yet. Gzipped dictionaries aren't
+    /// supported, since `compact_log` appends to the dictionary as plain text.
+    ///
+    /// # Arguments
+    ///
+    /// `dict_path` (`&str`) - Name of the file to parse for the dictionary.
+    ///
+    /// `log_path` (`&str`) - Name of the log file to replay and append to.
+    ///
+    /// # Return value
+    ///
+    /// Either the constructed `Autocompleter`, or an error string if either file
+    /// could not be read.
+    pub fn open_with_log(dict_path: &str, log_path: &str) -> Result<Autocompleter, String> {
+        if dict_path.ends_with(GZIP_EXTENSION) {
+            return Err(format!(
+                "Error opening file `{dict_path}`: a log can't be kept for a gzipped dictionary"
+            ));
+        }
+        let mut val = Autocompleter::from_file(dict_path)?;
+
+        let file = match fs_err::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .read(true)
+            .open(log_path)
+        {
             Ok(f) => f,
-            Err(e) => return Err(format!("Error opening file `{dict_filename}`: {e}")),
+            Err(e) => return Err(format!("Error opening log `{log_path}`: {e}")),
         };
-
-        // Read through the file line by line
-        let reader = BufReader::new(dict_file);
-        for line in reader.lines() {
-            match line {
-                Ok(l) => {
-                    for mut word in l.split_whitespace() {
-                        word = word.trim_end_matches(|c: char| c.is_ascii_punctuation());
-                        val.trie.add_record(word.to_string());
-                    }
-                }
-                Err(e) => return Err(format!("Error reading line from file: {e}")),
-            }
+        match file.try_clone() {
+            Ok(f) => val.read_words(BufReader::new(f), |_| {})?,
+            Err(e) => return Err(format!("Error reading log `{log_path}`: {e}")),
         }
 
+        val.log = WordLog {
+            dict_path: dict_path.to_string(),
+            log_path: log_path.to_string(),
+            file: Some(file),
+            error: None,
+        };
         Ok(val)
     }
 
-    /// Adds a word to the `Autocompleter`.
-    ///
-    /// Delegates to the underlying `Mwt` subroutine.
+    /// Constructs a new `Autocompleter` from any buffered reader, such as a
+    /// locked stdin, tokenized the same way as a file passed to `from_file`.
     ///
     /// # Arguments
     ///
-    /// `word` (`String`) - Word to add to the structure.
-    pub fn add_word(&mut self, word: String) {
-        self.trie.add_record(word);
+    /// `reader` (`R: BufRead`) - Source of the dictionary text.
+    ///
+    /// # Return value
+    ///
+    /// Either the constructed `Autocompleter`, or an error string if reading failed.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Autocompleter, String> {
+        let mut val = Autocompleter::new();
+        val.read_words(reader, |_| {})?;
+        Ok(val)
     }
 
-    /// Runs a prediction check for a given prefixed String.
+    /// Constructs a new `Autocompleter` from a string of words, tokenized the
+    /// same way as a file passed to `from_file`.
     ///
-    /// This prediction check is accomplished by traversing the MWT as
-    /// far down as possible, then it runs a depth-first search to traverse
-    /// the rest of the MWT to grab finished words.
+    /// # Arguments
+    ///
+    /// `text` (`&str`) - Whitespace separated words to add.
+    ///
+    /// # Return value
     ///
-    /// From there, the autocompleter returns the top 10 most popular words sorted
-    /// first on alphabetical order and second by the frequency.
+    /// The constructed `Autocompleter`.
+    pub fn from_text(text: &str) -> Autocompleter {
+        let mut val = Autocompleter::new();
+        // Reading from an in-memory buffer can't fail
+        let _ = val.read_words(text.as_bytes(), |_| {});
+        val
+    }
+
+    /// Constructs a new `Autocompleter` from a file like `from_file`, but
+    /// splits each line into words with a caller-supplied tokenizer instead of
+    /// the built-in whitespace splitting and punctuation trimming.
     ///
     /// # Arguments
     ///
-    /// `prefix` (`String`) - Word to search for, either complete or the beginning.
+    /// `dict_filename` (`&str`) - Name of the file to parse for the dictionary.
+    ///
+    /// `tokenizer` (`F: Fn(&str) -> Vec<String>`) - Called once per line (without its line
+    /// ending), returning the words to add from that line.
     ///
     /// # Return value
     ///
-    /// This function returns a vector of strings that corresponds to the predictions.
-    pub fn predict_completions(&self, prefix: &String) -> Vec<String> {
-        let mut res: Vec<String> = Vec::new();
-        let mut tmp = self.trie.get_root();
-
-        if prefix.len() >= MIN_LEN {
-            // Walk down the Trie as far as we can
-            for ch in prefix.chars() {
-                let children = tmp.get_children();
-
-                if !children.contains_key(&ch) {
-                    return res;
-                }
-                tmp = match children.get(&ch).unwrap() {
-                    Some(nd) => nd,
-                    None => panic!("Unreachable code hit: existing child had non-existing node!"),
+    /// Either the constructed `Autocompleter`, or a `Error` with the error string.
+    pub fn from_file_with<F: Fn(&str) -> Vec<String>>(
+        dict_filename: &str,
+        tokenizer: F,
+    ) -> Result<Autocompleter, String> {
+        let mut val = Autocompleter::new();
+        let reader = Autocompleter::open_dictionary(dict_filename)?;
+        let path = val.unloaded_path(dict_filename)?;
+        val.read_lines(
+            reader,
+            |_| {},
+            |ac, line| {
+                for word in tokenizer(line) {
+                    ac.insert(word);
                 }
-            }
-            // Run DFS to get all completion predictions
-            let mut dfs_results = Autocompleter::depth_first_search(Some(tmp));
+            },
+        )?;
+        // Record the file like `from_file` does, so adding it again is caught
+        val.loaded_files.insert(path);
+        Ok(val)
+    }
 
-            // Sort by alphabetical order first, then stable sort on frequency second
-            // Frequency sort should be reversed from largest to smallest
-            dfs_results.sort_unstable_by(|a, b| a.data.cmp(&b.data));
-            dfs_results.sort_by(|a, b| b.count.cmp(&a.count));
+    /// Constructs an `Autocompleter` from a file of `word<TAB>score` lines
+    /// with float scores, and ranks predictions by those scores. See
+    /// `add_from_weighted_file_f64`.
+    ///
+    /// # Arguments
+    ///
+    /// `path` (`&str`) - Path to the scored word list.
+    ///
+    /// # Return value
+    ///
+    /// Either the `Autocompleter` or an error string describing what went wrong.
+    pub fn from_weighted_file_f64(path: &str) -> Result<Autocompleter, String> {
+        let mut val = Autocompleter::builder().float_scores(true).build();
+        val.add_from_weighted_file_f64(path)?;
+        Ok(val)
+    }
 
-            let num_to_ret = if dfs_results.len() < ELEMENTS_TO_RETURN {
-                dfs_results.len()
-            } else {
-                ELEMENTS_TO_RETURN
-            };
+    /// Constructs a new `Autocompleter` from a file like `from_file`, while
+    /// periodically reporting how far through the file loading has got.
+    ///
+    /// The callback is invoked roughly every 64 KiB, and once more when the
+    /// whole file has been read. For `.gz` files the count is of decompressed
+    /// bytes, so it will overshoot the size of the file on disk.
+    ///
+    /// # Arguments
+    ///
+    /// `dict_filename` (`&str`) - Name of the file to parse for the dictionary.
+    ///
+    /// `on_progress` (`F: FnMut(u64)`) - Called with the number of bytes read so far.
+    ///
+    /// # Return value
+    ///
+    /// Either the constructed `Autocompleter`, or a `Error` with the error string.
+    pub fn from_file_with_progress<F: FnMut(u64)>(
+        dict_filename: &str,
+        on_progress: F,
+    ) -> Result<Autocompleter, String> {
+        let mut val = Autocompleter::new();
+        let reader = Autocompleter::open_dictionary(dict_filename)?;
+        val.read_words(reader, on_progress)?;
+        Ok(val)
+    }
 
-            for ind in 0..num_to_ret {
-                res.push(dfs_results[ind].data.clone());
-            }
-        }
-        res
+    /// Constructs a new `Autocompleter` from a file like `from_file`, while
+    /// periodically reporting the most popular words loaded so far, e.g. to
+    /// show what's trending during a live ingest.
+    ///
+    /// Every snapshot is taken with `most_popular`, which in the worst case
+    /// looks at every word stored so far, so the cost of each one grows with
+    /// the dictionary. Keep `every_lines` large, in the thousands or more, so
+    /// snapshots don't dominate the loading time.
+    ///
+    /// # Arguments
+    ///
+    /// `dict_filename` (`&str`) - Name of the file to parse for the dictionary.
+    ///
+    /// `every_lines` (`usize`) - Number of lines read between snapshots. With 0, only the
+    /// final snapshot is taken.
+    ///
+    /// `n` (`usize`) - Number of words in each snapshot.
+    ///
+    /// `on_snapshot` (`F: FnMut(&[(String, i32)])`) - Called with up to `n` `(word, rank)`
+    /// pairs, most frequent first, after every `every_lines` lines and once more when the
+    /// whole file has been read.
+    ///
+    /// # Return value
+    ///
+    /// Either the constructed `Autocompleter`, or an error string if the file
+    /// could not be read.
+    pub fn from_file_with_snapshots<F: FnMut(&[(String, i32)])>(
+        dict_filename: &str,
+        every_lines: usize,
+        n: usize,
+        mut on_snapshot: F,
+    ) -> Result<Autocompleter, String> {
+        let mut val = Autocompleter::new();
+        let reader = Autocompleter::open_dictionary(dict_filename)?;
+        let mut lines: usize = 0;
+        val.read_lines(
+            reader,
+            |_| {},
+            |ac, line| {
+                ac.add_line_words(line);
+                lines += 1;
+                if every_lines > 0 && lines.is_multiple_of(every_lines) {
+                    on_snapshot(&ac.most_popular(n));
+                }
+            },
+        )?;
+        on_snapshot(&val.most_popular(n));
+        Ok(val)
     }
 
-    /// This function is used in the second half of `predict_completions`.
-    /// Once the correct ending node of the prefix is found, we recursively
-    /// search the rest of the Trie looking for all completed words and add
-    /// them to the return vector.
+    /// Opens a dictionary file for buffered reading, transparently
+    /// decompressing it if its name ends in `.gz`.
     ///
     /// # Arguments
     ///
-    /// `node` (`Option<&Box<MwtNode>>`) - Current node in the MWT we are searching
+    /// `dict_filename` (`&str`) - Name of the file to open.
     ///
     /// # Return value
     ///
-    /// A vector of tuples, where the first value is the frequency and the second is the
-    /// word corresponding to that frequency.
-    fn depth_first_search(node: Option<&Box<MwtNode>>) -> Vec<SortResult> {
-        let mut ret: Vec<SortResult> = Vec::new();
-        if let Some(nd) = node {
-            let children = nd.get_children();
+    /// Either a reader over the file's text, or an error string.
+    fn open_dictionary(dict_filename: &str) -> Result<Box<dyn BufRead>, String> {
+        // Try to open the file for reading, or bail out if an error occurs.
+        let dict_file = match File::open(dict_filename) {
+            Ok(f) => f,
+            Err(e) => return Err(format!("Error opening file `{dict_filename}`: {e}")),
+        };
 
-            if nd.get_end() {
-                ret.push(SortResult::new(nd.get_rank(), nd.get_data().to_string()));
-            }
+        if dict_filename.ends_with(GZIP_EXTENSION) {
+            #[cfg(feature = "gzip")]
+            return Ok(Box::new(BufReader::new(GzDecoder::new(dict_file))));
+            #[cfg(not(feature = "gzip"))]
+            return Err(format!(
+                "Error opening file `{dict_filename}`: gzip support requires the `gzip` feature"
+            ));
+        }
+
+        Ok(Box::new(BufReader::new(dict_file)))
+    }
+
+    /// Formats an error found while loading a line of a file, so every
+    /// loader reports the line the same way.
+    ///
+    /// # Arguments
+    ///
+    /// `line_number` (`usize`) - Line the error occurred on, starting from 1.
+    ///
+    /// `reason` (`impl Display`) - What went wrong.
+    ///
+    /// # Return value
+    ///
+    /// A message of the form `error on line 4217: ...`.
+    fn line_error(line_number: usize, reason: impl Display) -> String {
+        format!("error on line {line_number}: {reason}")
+    }
+
+    /// Constructs a new, empty `Autocompleter` with the default settings and
+    /// room reserved for `capacity` words as described in `reserve`.
+    ///
+    /// # Arguments
+    ///
+    /// `capacity` (`usize`) - Number of words expected.
+    pub fn with_capacity(capacity: usize) -> Autocompleter {
+        let mut val = Autocompleter::new();
+        val.reserve(capacity);
+        val
+    }
+
+    /// Works out how many nodes the MWT would have after inserting the given
+    /// words, without building it, to judge memory use before loading a big
+    /// word list. See `Mwt::estimate_nodes`.
+    ///
+    /// Words are counted as given, so with options that change inserted words,
+    /// such as `case_insensitive` or `max_word_len`, the real count can be
+    /// lower.
+    ///
+    /// # Arguments
+    ///
+    /// `words` (`I: IntoIterator<Item = &str>`) - Words that would be inserted.
+    ///
+    /// # Return value
+    ///
+    /// Number of nodes, including the root, comparable to `node_count`.
+    pub fn estimate_nodes<'a, I: IntoIterator<Item = &'a str>>(words: I) -> usize {
+        Mwt::estimate_nodes(words)
+    }
+
+    /// Checks whether `ignore_punctuation` strips a character.
+    ///
+    /// # Arguments
+    ///
+    /// `ch` (`char`) - Character to check.
+    ///
+    /// # Return value
+    ///
+    /// `true` for ASCII punctuation and whitespace.
+    fn is_ignored(ch: char) -> bool {
+        ch.is_ascii_punctuation() || ch.is_whitespace()
+    }
+
+    /// Constructs a new `Autocompleter` from a CSV file of `word,count` rows.
+    ///
+    /// The first row is treated as a header and skipped. Each word is given
+    /// the rank in its count column directly, and a word appearing on several
+    /// rows has its counts summed. Quoting is handled by the CSV parser, so
+    /// words may contain commas.
+    ///
+    /// # Arguments
+    ///
+    /// `path` (`&str`) - Name of the CSV file to read.
+    ///
+    /// # Return value
+    ///
+    /// Either the constructed `Autocompleter`, or an error string of the form
+    /// `error on row 4217: ...` naming the row and the problem, in the same
+    /// shape as the line errors of the other loaders.
+    #[cfg(feature = "csv")]
+    pub fn from_csv(path: &str) -> Result<Autocompleter, String> {
+        let mut val = Autocompleter::new();
+
+        let csv_file = match File::open(path) {
+            Ok(f) => f,
+            Err(e) => return Err(format!("Error opening file `{path}`: {e}")),
+        };
+
+        let mut reader = csv::Reader::from_reader(csv_file);
+        for (idx, record) in reader.records().enumerate() {
+            // Row 1 is the header, so data rows start at 2.
+            let row = idx + 2;
+            let record = match record {
+                Ok(r) => r,
+                Err(e) => return Err(format!("error on row {row}: {e}")),
+            };
+            if record.len() != 2 {
+                return Err(format!(
+                    "error on row {row}: expected 2 columns, found {}",
+                    record.len()
+                ));
+            }
+            let count = match record[1].trim().parse::<i32>() {
+                Ok(c) => c,
+                Err(e) => return Err(format!("error on row {row}: {e}")),
+            };
+            val.add_word_with_count(record[0].to_string(), count);
+        }
+
+        Ok(val)
+    }
+
+    /// Checks whether a prefix of `word` ends between two grapheme clusters,
+    /// rather than partway through one such as "e" followed by a combining
+    /// accent. Without the `unicode-segmentation` feature every position counts.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`&str`) - Stored word beginning with the prefix.
+    ///
+    /// `at` (`usize`) - Byte length of the prefix.
+    ///
+    /// # Return value
+    ///
+    /// `true` if `at` falls on a grapheme cluster boundary of `word`.
+    fn on_grapheme_boundary(word: &str, at: usize) -> bool {
+        #[cfg(feature = "unicode-segmentation")]
+        return GraphemeCursor::new(at, word.len(), true)
+            .is_boundary(word, 0)
+            .unwrap_or(true);
+        #[cfg(not(feature = "unicode-segmentation"))]
+        return word.is_char_boundary(at);
+    }
+}
+
+impl<T> Autocompleter<T> {
+    /// Canonicalizes the path of a dictionary file about to be loaded, and
+    /// checks it hasn't been loaded already. Callers record the path in
+    /// `loaded_files` once loading has succeeded.
+    ///
+    /// # Arguments
+    ///
+    /// `dict_filename` (`&str`) - Name of the file, which must exist.
+    ///
+    /// # Return value
+    ///
+    /// Either the canonical path, or an error string if the file can't be
+    /// found or has already been loaded.
+    fn unloaded_path(&self, dict_filename: &str) -> Result<PathBuf, String> {
+        let path = fs_err::canonicalize(dict_filename).map_err(|e| e.to_string())?;
+        if self.loaded_files.contains(&path) {
+            return Err(format!(
+                "File `{dict_filename}` has already been loaded, skipping it so its words aren't counted twice"
+            ));
+        }
+        Ok(path)
+    }
+
+    /// Adds every word from a given file to the `Autocompleter`, on top of
+    /// whatever is already stored.
+    ///
+    /// Files whose name ends in `.gz` are decompressed on the fly when the crate
+    /// is built with the `gzip` feature, and rejected otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// `dict_filename` (`&str`) - Name of the file to parse for the dictionary.
+    ///
+    /// # Return value
+    ///
+    /// Either nothing, or an error string of the form `error on line 4217: ...`
+    /// naming the line that could not be read. Words read before an error
+    /// occurred remain in the `Autocompleter`.
+    ///
+    /// Loading the same file twice is also an error, since it would silently
+    /// double every rank: the second load is skipped and nothing changes. Files
+    /// are compared by canonical path, so different spellings of the same path
+    /// are caught too. A file is only recorded once it has loaded without
+    /// errors, so a failed load can be retried. `clear` forgets which files
+    /// have been loaded.
+    pub fn add_from_file(&mut self, dict_filename: &str) -> Result<(), String> {
+        let reader = Autocompleter::open_dictionary(dict_filename)?;
+        let path = self.unloaded_path(dict_filename)?;
+        self.read_words(reader, |_| {})?;
+        self.loaded_files.insert(path);
+        Ok(())
+    }
+
+    /// Adds every word in a file to the `Autocompleter` like `add_from_file`,
+    /// but all or nothing: the whole file is parsed into a separate
+    /// `Autocompleter` first, and only merged in once parsing has fully
+    /// succeeded. If a line can't be read (e.g. it isn't valid UTF-8), the
+    /// `Autocompleter` is left exactly as it was.
+    ///
+    /// Words are tokenized, folded and stemmed with this `Autocompleter`'s
+    /// settings. Each word's count from the file is merged in as if with
+    /// `add_word_with_count`, so `max_words` still applies to new words, but
+    /// recency scores aren't decayed per insertion. The spellings recorded
+    /// under `preserve_case` or `ignore_punctuation` and document frequencies
+    /// are merged too, and with `open_with_log` the merged words are appended
+    /// to the log.
+    ///
+    /// # Arguments
+    ///
+    /// `dict_filename` (`&str`) - Name of the file to parse for the dictionary.
+    ///
+    /// # Return value
+    ///
+    /// Either the number of distinct words read from the file, or an error
+    /// string if the file could not be read. Like `add_from_file`, loading a
+    /// file that has already been loaded is an error.
+    pub fn merge_from_file(&mut self, dict_filename: &str) -> Result<usize, String> {
+        let reader = Autocompleter::open_dictionary(dict_filename)?;
+        let path = self.unloaded_path(dict_filename)?;
+
+        let mut staged = self.empty_like();
+        staged.read_words(reader, |_| {})?;
+
+        // The staged words have already been prepared, so they go straight
+        // into the trie rather than being folded or stemmed a second time
+        let mut added = 0;
+        let keep_log = self.log.file.is_some();
+        let mut logged: Vec<(Cow<'_, str>, i32)> = Vec::new();
+        Mwt::for_each_word(staged.trie.get_root(), "", false, |word, nd| {
+            self.trie.merge_node(word.to_string(), nd);
+            added += 1;
+            if keep_log {
+                // Log words the way the file spelled them, so a replay
+                // records the same spellings
+                match nd.get_surface() {
+                    Some(_) => logged.extend(nd.surfaces_iter().map(|(s, n)| (s.into(), n))),
+                    None => logged.push((word.to_string().into(), nd.get_rank())),
+                }
+            }
+        });
+        self.log.append_counted(&logged);
+        self.loaded_files.insert(path);
+        Ok(added)
+    }
+
+    /// Constructs an empty `Autocompleter` that prepares words the same way as
+    /// this one, for staging words before merging them in. No word cap is
+    /// set, since the cap only matters once the words are merged.
+    fn empty_like(&self) -> Autocompleter<T> {
+        let mut settings = self.settings.clone();
+        settings.max_words = None;
+        AutocompleterBuilder::from_settings(settings).build_with_payloads()
+    }
+
+    /// Adds every word in a file to the `Autocompleter` like `add_from_file`,
+    /// but skips lines that aren't valid UTF-8 instead of stopping at the
+    /// first one, so a single corrupt line doesn't abort loading a huge file.
+    ///
+    /// # Arguments
+    ///
+    /// `dict_filename` (`&str`) - Name of the file to parse for the dictionary.
+    ///
+    /// `on_skip` (`F: FnMut(usize, &str)`) - Called for each skipped line with its line
+    /// number, starting from 1, and the error it was skipped for, e.g. for logging. The
+    /// error reads like the ones the strict loaders return, e.g. `error on line 4217: ...`.
+    ///
+    /// # Return value
+    ///
+    /// Either the number of lines loaded and the number skipped, or an error
+    /// string if the file could not be opened or read at all. Like
+    /// `add_from_file`, loading a file that has already been loaded is an error.
+    pub fn add_from_file_lenient<F: FnMut(usize, &str)>(
+        &mut self,
+        dict_filename: &str,
+        on_skip: F,
+    ) -> Result<(usize, usize), String> {
+        let reader = Autocompleter::open_dictionary(dict_filename)?;
+        let path = self.unloaded_path(dict_filename)?;
+        let counts =
+            self.scan_lines(reader, |_| {}, Some(on_skip), Autocompleter::add_line_words)?;
+        self.loaded_files.insert(path);
+        Ok(counts)
+    }
+
+    /// Adds the words of a file of `word<TAB>score` lines, where each score
+    /// is a float such as a normalized probability or a log-likelihood.
+    /// Empty lines are skipped. A word listed more than once has its scores
+    /// summed.
+    ///
+    /// Every listed word also gets its rank increased by one, so it is stored
+    /// like any other word, but the rank says nothing about its score. Scores
+    /// only decide the ranking when the `Autocompleter` has the
+    /// `float_scores` option, which `from_weighted_file_f64` turns on; in
+    /// that mode, words that were never given a score count as 0.
+    ///
+    /// # Arguments
+    ///
+    /// `path` (`&str`) - Path to the scored word list.
+    ///
+    /// # Return value
+    ///
+    /// Either nothing or an error string of the form `error on line 4217: ...`
+    /// for the first line that couldn't be read or parsed. Lines before it have
+    /// already been added.
+    pub fn add_from_weighted_file_f64(&mut self, path: &str) -> Result<(), String> {
+        let reader = Autocompleter::open_dictionary(path)?;
+        let canonical = self.unloaded_path(path)?;
+
+        for (idx, line) in reader.lines().enumerate() {
+            let line_number = idx + 1;
+            let line = match line {
+                Ok(l) => l,
+                Err(e) => return Err(Autocompleter::line_error(line_number, e)),
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (word, score) = match line.split_once('\t') {
+                Some(pair) => pair,
+                None => {
+                    return Err(Autocompleter::line_error(
+                        line_number,
+                        "expected `word<TAB>score`",
+                    ))
+                }
+            };
+            let score = match score.trim().parse::<f64>() {
+                Ok(s) if s.is_finite() => s,
+                Ok(s) => {
+                    return Err(Autocompleter::line_error(
+                        line_number,
+                        format!("score {s} is not finite"),
+                    ))
+                }
+                Err(e) => {
+                    return Err(Autocompleter::line_error(
+                        line_number,
+                        format!("invalid score: {e}"),
+                    ))
+                }
+            };
+            let word = word.trim_start_matches(BOM);
+            self.add_word_with_score(word.to_string(), score);
+        }
+        self.loaded_files.insert(canonical);
+        Ok(())
+    }
+
+    /// Adds every line of a file to the `Autocompleter` as a phrase, on top
+    /// of whatever is already stored. Unlike `add_from_file`, lines aren't
+    /// split into words, so a line reading "machine learning" is stored as
+    /// that one phrase. See `add_phrase`.
+    ///
+    /// # Arguments
+    ///
+    /// `dict_filename` (`&str`) - Name of the file to read phrases from, one per line.
+    ///
+    /// # Return value
+    ///
+    /// Either nothing, or an error string if the file could not be read. Like
+    /// `add_from_file`, loading a file that has already been loaded is an error.
+    pub fn add_phrases_from_file(&mut self, dict_filename: &str) -> Result<(), String> {
+        let reader = Autocompleter::open_dictionary(dict_filename)?;
+        let path = self.unloaded_path(dict_filename)?;
+        self.read_lines(reader, |_| {}, |ac, line| ac.add_phrase(line))?;
+        self.loaded_files.insert(path);
+        Ok(())
+    }
+
+    /// Reads through a source line by line, splitting each line on whitespace
+    /// and adding every resulting word with trailing punctuation trimmed,
+    /// except for any characters in `keep_chars`.
+    ///
+    /// # Arguments
+    ///
+    /// `reader` (`R: BufRead`) - Source of the dictionary text.
+    ///
+    /// `on_progress` (`P: FnMut(u64)`) - Progress callback, see `read_lines`.
+    ///
+    /// # Return value
+    ///
+    /// Either nothing, or an error string if a line could not be read.
+    fn read_words<R, P>(&mut self, reader: R, on_progress: P) -> Result<(), String>
+    where
+        R: BufRead,
+        P: FnMut(u64),
+    {
+        self.read_lines(reader, on_progress, Autocompleter::add_line_words)
+    }
+
+    /// Splits a line on whitespace, and on digits too with `split_on_digits`,
+    /// and adds every resulting word with trailing punctuation trimmed,
+    /// except for any characters in `keep_chars`. This is the tokenizer
+    /// behind `read_words`.
+    ///
+    /// When `track_doc_freq` is set, the line also counts once towards the
+    /// document frequency of each distinct word on it.
+    ///
+    /// # Arguments
+    ///
+    /// `line` (`&str`) - Line of dictionary text, without its line ending.
+    fn add_line_words(&mut self, line: &str) {
+        let mut seen: HashSet<String> = HashSet::new();
+        let split_on_digits = self.settings.split_on_digits;
+        let words = line
+            .split_whitespace()
+            .flat_map(|token| token.split(move |c: char| split_on_digits && c.is_numeric()));
+        for mut word in words {
+            word = word.trim_end_matches(|c: char| {
+                c.is_ascii_punctuation() && !self.settings.keep_chars.contains(&c)
+            });
+            // Tokens made only of punctuation (or digits) trim down to nothing
+            if word.is_empty() {
+                continue;
+            }
+            if self.settings.track_doc_freq {
+                let decay = self.settings.recency_decay;
+                self.insert_with(word.to_string(), 1, |trie, word| {
+                    let first_in_line = seen.insert(word.clone());
+                    trie.add_record_in_line(word, decay, first_in_line);
+                });
+            } else {
+                self.insert(word.to_string());
+            }
+        }
+    }
+
+    /// Reads through a source line by line, handing each line to a callback
+    /// along with the `Autocompleter` to add words to.
+    ///
+    /// # Arguments
+    ///
+    /// `reader` (`R: BufRead`) - Source of the dictionary text.
+    ///
+    /// `on_progress` (`P: FnMut(u64)`) - Called with the number of bytes read so far,
+    /// every `PROGRESS_INTERVAL` bytes and once at the end.
+    ///
+    /// `handle_line` (`F: FnMut(&mut Autocompleter, &str)`) - Called once per line,
+    /// without its line ending. A byte order mark at the start of the source is
+    /// dropped too.
+    ///
+    /// # Return value
+    ///
+    /// Either nothing, or an error string if a line could not be read.
+    fn read_lines<R, P, F>(
+        &mut self,
+        reader: R,
+        on_progress: P,
+        handle_line: F,
+    ) -> Result<(), String>
+    where
+        R: BufRead,
+        P: FnMut(u64),
+        F: FnMut(&mut Autocompleter<T>, &str),
+    {
+        self.scan_lines(reader, on_progress, None::<fn(usize, &str)>, handle_line)
+            .map(|_| ())
+    }
+
+    /// Shared line reading logic behind `read_lines` and the lenient loaders.
+    ///
+    /// # Arguments
+    ///
+    /// `reader` (`R: BufRead`) - Source of the dictionary text.
+    ///
+    /// `on_progress` (`P: FnMut(u64)`) - Progress callback, see `read_lines`.
+    ///
+    /// `on_skip` (`Option<S: FnMut(usize, &str)>`) - If set, lines that aren't valid UTF-8
+    /// are skipped instead of ending the read, and this is called with each one's
+    /// line number (starting from 1) and its error.
+    ///
+    /// `handle_line` (`F: FnMut(&mut Autocompleter, &str)`) - Line callback, see `read_lines`.
+    ///
+    /// # Return value
+    ///
+    /// Either the number of lines handled and the number skipped, or an error
+    /// string of the form `error on line 4217: ...` if a line could not be read.
+    fn scan_lines<R, P, S, F>(
+        &mut self,
+        mut reader: R,
+        mut on_progress: P,
+        mut on_skip: Option<S>,
+        mut handle_line: F,
+    ) -> Result<(usize, usize), String>
+    where
+        R: BufRead,
+        P: FnMut(u64),
+        S: FnMut(usize, &str),
+        F: FnMut(&mut Autocompleter<T>, &str),
+    {
+        let mut raw: Vec<u8> = Vec::new();
+        let mut bytes_read: u64 = 0;
+        let mut next_report = PROGRESS_INTERVAL;
+        let mut line_number = 0;
+        let mut skipped = 0;
+        loop {
+            raw.clear();
+            let n = match reader.read_until(b'\n', &mut raw) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) => return Err(Autocompleter::line_error(line_number + 1, e)),
+            };
+            line_number += 1;
+            let first_line = bytes_read == 0;
+            bytes_read += n as u64;
+            if bytes_read >= next_report {
+                on_progress(bytes_read);
+                next_report = bytes_read + PROGRESS_INTERVAL;
+            }
+            let line = match std::str::from_utf8(&raw) {
+                Ok(l) => l,
+                Err(e) => {
+                    let error = Autocompleter::line_error(line_number, e);
+                    match on_skip.as_mut() {
+                        Some(skip) => {
+                            skip(line_number, &error);
+                            skipped += 1;
+                            continue;
+                        }
+                        None => return Err(error),
+                    }
+                }
+            };
+            let mut content = line
+                .strip_suffix('\n')
+                .map(|l| l.strip_suffix('\r').unwrap_or(l))
+                .unwrap_or(line);
+            // Files saved by some Windows editors start with a byte order mark,
+            // which would otherwise stick to the first word
+            if first_line {
+                content = content.strip_prefix(BOM).unwrap_or(content);
+            }
+            handle_line(self, content);
+        }
+        on_progress(bytes_read);
+        Ok((line_number - skipped, skipped))
+    }
+
+    /// Prepares for adding roughly `additional` more words, to cut down on
+    /// rehashing during a bulk load.
+    ///
+    /// This is only a hint. Just the root of the MWT can be sized ahead of
+    /// time, since the maps below it depend on which words arrive, so the gain
+    /// is modest.
+    ///
+    /// # Arguments
+    ///
+    /// `additional` (`usize`) - Number of words about to be added.
+    pub fn reserve(&mut self, additional: usize) {
+        self.trie.reserve(additional);
+    }
+
+    /// Replaces the built-in ordering of predictions with a scoring function.
+    /// Every completion is scored from its word and rank, and completions are
+    /// returned highest score first, with alphabetical order breaking ties.
+    /// The `SortOrder`, `TieBreak` and recency ranking are ignored while a
+    /// ranker is set. This covers strategies like length penalties or
+    /// blending in an external popularity signal, for example
+    /// `ac.set_ranker(|word, rank| rank as f64 / word.len() as f64)`.
+    ///
+    /// The ranker is called several times per completion while sorting, so it
+    /// should be cheap. Every completion under a prefix has to be scored, so
+    /// predictions can't skip branches early while a ranker is set.
+    ///
+    /// # Arguments
+    ///
+    /// `ranker` (`F: Fn(&str, i32) -> f64`) - Scores a completion from its word and rank.
+    /// Higher scores rank first.
+    pub fn set_ranker<F>(&mut self, ranker: F)
+    where
+        F: Fn(&str, i32) -> f64 + Send + Sync + 'static,
+    {
+        self.settings.ranker = Some(Ranker(Arc::new(ranker)));
+    }
+
+    /// Removes a ranker set with `set_ranker`, going back to the configured
+    /// `SortOrder`.
+    pub fn clear_ranker(&mut self) {
+        self.settings.ranker = None;
+    }
+
+    /// Reclaims memory left over after many removals, e.g. from `retain` or
+    /// `trim_to_top_k`. Every map in the MWT is shrunk to fit the children it
+    /// still holds, and the per-node rank bounds used to prune predictions are
+    /// recomputed exactly, since lowering ranks in place can leave them loose.
+    ///
+    /// Branches that no longer lead to a word are already pruned as words are
+    /// removed, so the node count doesn't change. Words are compacted in
+    /// place rather than reinserted into a new MWT, which keeps their ranks,
+    /// recency scores, sources and eviction order intact. Predictions are
+    /// unaffected.
+    pub fn compact(&mut self) {
+        self.trie.compact();
+    }
+
+    /// Removes every stored word, keeping the settings the `Autocompleter`
+    /// was built with.
+    pub fn clear(&mut self) {
+        self.trie.clear();
+        self.loaded_files.clear();
+    }
+
+    /// Returns the number of distinct words stored in the `Autocompleter`.
+    pub fn len(&self) -> usize {
+        self.trie.len()
+    }
+
+    /// Returns `true` if no words are stored in the `Autocompleter`.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds a word to the `Autocompleter`.
+    ///
+    /// Delegates to the underlying `Mwt` subroutine. Empty strings are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`String`) - Word to add to the structure.
+    pub fn add_word(&mut self, word: String) {
+        self.log.append(&word);
+        self.insert(word);
+    }
+
+    /// Adds a word like `add_word`, and reports the word's rank afterwards so
+    /// callers don't need a second lookup.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`String`) - Word to add to the structure.
+    ///
+    /// # Return value
+    ///
+    /// The word's new rank, or 0 if the word was ignored or rejected.
+    pub fn add_word_counted(&mut self, word: String) -> i32 {
+        self.log.append(&word);
+        self.insert(word)
+    }
+
+    /// Adds a multi-word phrase, such as "new york", to the `Autocompleter`.
+    ///
+    /// The phrase is stored whole, with any run of whitespace collapsed to a
+    /// single space and none at either end, so it completes from any prefix
+    /// of it. Phrases can be mixed with plain words; `predict_phrases` limits
+    /// predictions to phrases only. The file loaders other than
+    /// `add_phrases_from_file` always split on whitespace, so they never
+    /// produce phrases.
+    ///
+    /// # Arguments
+    ///
+    /// `phrase` (`&str`) - Phrase to add. Blank phrases are ignored.
+    pub fn add_phrase(&mut self, phrase: &str) {
+        let phrase: Vec<&str> = phrase.split_whitespace().collect();
+        self.insert(phrase.join(" "));
+    }
+
+    /// Adds every word from a collection to the `Autocompleter`.
+    ///
+    /// Equivalent to calling `add_word` once per item, so a word that appears
+    /// several times still has its rank increased for each appearance.
+    ///
+    /// # Arguments
+    ///
+    /// `words` (`I: IntoIterator<Item = String>`) - Words to add to the structure.
+    pub fn add_words<I: IntoIterator<Item = String>>(&mut self, words: I) {
+        for word in words {
+            self.log.append(&word);
+            self.insert(word);
+        }
+    }
+
+    /// Adds a word with a float score, such as a probability or a
+    /// log-likelihood from a language model. The word's rank goes up by one
+    /// as usual and `score` is added to its float score. Only used for
+    /// ranking with the `float_scores` option.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`String`) - Word to add.
+    ///
+    /// `score` (`f64`) - Amount to add to the word's score.
+    pub fn add_word_with_score(&mut self, word: String, score: f64) {
+        self.insert_with(word, 1, |trie, word| {
+            trie.add_record_with_score(word, score)
+        });
+    }
+
+    /// Adds a word to the `Autocompleter` as if it had been added `count`
+    /// times. Useful for loading pre-counted data.
+    ///
+    /// Empty words and non-positive counts are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`String`) - Word to add to the structure.
+    ///
+    /// `count` (`i32`) - Amount to increase the word's rank by.
+    pub fn add_word_with_count(&mut self, word: String, count: i32) {
+        self.insert_with(word, count.max(0), |trie, word| {
+            trie.add_record_with_count(word, count)
+        });
+    }
+
+    /// Adds a word to the `Autocompleter` like `add_word`, and records that it
+    /// came from the source identified by `source_id`, such as a note or line
+    /// number. `predict_with_sources` then reports where each completion was
+    /// seen, making the `Autocompleter` a lightweight inverted index.
+    ///
+    /// Sources are only recorded when the `Autocompleter` was built with
+    /// `track_sources` enabled. Otherwise this behaves exactly like `add_word`.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`String`) - Word to add to the structure.
+    ///
+    /// `source_id` (`u64`) - Identifier of the source the word came from.
+    pub fn add_word_with_source(&mut self, word: String, source_id: u64) {
+        if !self.settings.track_sources {
+            self.insert(word);
+            return;
+        }
+        let decay = self.settings.recency_decay;
+        self.insert_with(word, 1, |trie, word| {
+            trie.add_record_with_source(word, decay, source_id)
+        });
+    }
+
+    /// Adds a word to the `Autocompleter` like `add_word`, and attaches a
+    /// payload to it, such as a category, an ID or a display label, replacing
+    /// any payload it had. `predict_with_payloads` then returns each
+    /// completion together with its payload, e.g. for a command palette that
+    /// maps names back to commands.
+    ///
+    /// The payload type is chosen when building, with
+    /// `AutocompleterBuilder::build_with_payloads`. A plain `Autocompleter`
+    /// has payloads of type `()`.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`String`) - Word to add to the structure.
+    ///
+    /// `payload` (`T`) - Data to attach to the word.
+    pub fn add_word_with_payload(&mut self, word: String, payload: T) {
+        let decay = self.settings.recency_decay;
+        self.insert_with(word, 1, |trie, word| {
+            trie.add_record_with_payload(word, decay, payload)
+        });
+    }
+
+    /// Sets a word's rank to an exact value rather than increasing it, e.g. to
+    /// load authoritative popularity figures. The word is added if it isn't
+    /// stored yet.
+    ///
+    /// A rank of 0 (or less) removes the word entirely, the same as
+    /// `remove_word`, since a stored word always has a rank of at least 1.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`&str`) - Word to update.
+    ///
+    /// `rank` (`i32`) - New rank.
+    ///
+    /// # Return value
+    ///
+    /// `true` if the word was already stored beforehand.
+    pub fn set_rank(&mut self, word: &str, rank: i32) -> bool {
+        self.insert_with(word.to_string(), 0, |trie, word| {
+            trie.set_record_rank(word, rank)
+        })
+    }
+
+    /// Single entry point every insertion goes through, so that settings
+    /// affecting insertion are applied consistently.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`String`) - Word to add to the structure.
+    ///
+    /// # Return value
+    ///
+    /// The word's new rank, or 0 if the word was ignored or rejected.
+    fn insert(&mut self, word: String) -> i32 {
+        let decay = self.settings.recency_decay;
+        self.insert_with(word, 1, |trie, word| match decay {
+            Some(decay) => trie.add_record_decayed(word, decay),
+            None => trie.add_record(word),
+        })
+    }
+
+    /// Renames a stored word, e.g. to fix a word that was consistently
+    /// misspelled in the source text, without losing how often it was seen.
+    /// `old`'s rank moves to `new`, summed with `new`'s own rank if it is
+    /// already stored, and `old` is removed along with any branch that only
+    /// led to it. Its document frequency, float score and sources move too.
+    ///
+    /// `new` is prepared like any added word, so it is case folded or stemmed
+    /// according to the settings, and with `ignore_punctuation` or
+    /// `preserve_case` the old spelling is forgotten in favour of `new`'s.
+    /// The renamed word counts as freshly inserted for recency and the
+    /// eviction order.
+    ///
+    /// # Arguments
+    ///
+    /// `old` (`&str`) - Word to rename.
+    ///
+    /// `new` (`&str`) - New spelling of the word.
+    ///
+    /// # Return value
+    ///
+    /// `true` if `old` was stored and has been renamed. If `new` is empty or
+    /// longer than `max_word_len`, nothing changes and `false` is returned.
+    pub fn rename(&mut self, old: &str, new: &str) -> bool {
+        let old = self.fold(old).into_owned();
+        let Some(rank) = self
+            .trie
+            .find_node(&old)
+            .filter(|nd| nd.get_end())
+            .map(|nd| nd.get_rank())
+        else {
+            return false;
+        };
+        self.insert_with(new.to_string(), rank, |trie, new| {
+            trie.rename_record(&old, new)
+        })
+    }
+
+    /// Removes a word from the `Autocompleter` entirely, whatever its rank.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`&str`) - Word to remove.
+    ///
+    /// # Return value
+    ///
+    /// `true` if the word was stored and has been removed.
+    pub fn remove_word(&mut self, word: &str) -> bool {
+        let word = self.fold(word).into_owned();
+        self.trie.remove_record(&word)
+    }
+
+    /// Removes every word starting with a prefix at once, including the
+    /// prefix itself if it is a word, e.g. to drop a whole category such as
+    /// "un". The branch below the prefix is cut off in one go, which is much
+    /// faster than removing its words one by one.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Beginning shared by the words to remove. An empty
+    /// prefix removes every word.
+    ///
+    /// # Return value
+    ///
+    /// Number of distinct words removed.
+    pub fn remove_prefix(&mut self, prefix: &str) -> usize {
+        let prefix = self.fold(prefix).into_owned();
+        self.trie.remove_prefix(&prefix)
+    }
+
+    /// Removes every word from a collection entirely, whatever their ranks.
+    /// The counterpart to `add_words`, e.g. for pruning against a blocklist.
+    ///
+    /// Each removal only walks the word's own path, so the cost depends on the
+    /// length of the collection rather than the size of the dictionary.
+    /// Words that aren't stored, or appear more than once, are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// `words` (`I: IntoIterator<Item = String>`) - Words to remove.
+    ///
+    /// # Return value
+    ///
+    /// Number of words that were stored and have been removed.
+    pub fn remove_words<I: IntoIterator<Item = String>>(&mut self, words: I) -> usize {
+        words
+            .into_iter()
+            .filter(|word| {
+                let word = self.fold(word).into_owned();
+                self.trie.remove_record(&word)
+            })
+            .count()
+    }
+
+    /// Keeps only the words for which `pred` returns `true`, removing the rest.
+    ///
+    /// For example `ac.retain(|_, rank| rank >= 5)` drops every word seen
+    /// fewer than 5 times, and `ac.retain(|word, _| word.len() <= 12)` drops
+    /// long words.
+    ///
+    /// # Arguments
+    ///
+    /// `pred` (`F: FnMut(&str, i32) -> bool`) - Called with each word and its rank.
+    ///
+    /// # Return value
+    ///
+    /// Number of words removed.
+    pub fn retain<F: FnMut(&str, i32) -> bool>(&mut self, pred: F) -> usize {
+        self.trie.retain(pred)
+    }
+
+    /// Removes a single occurrence of a word, lowering its rank by one. The
+    /// word is removed entirely once its rank reaches zero.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`&str`) - Word to decrement.
+    ///
+    /// # Return value
+    ///
+    /// The word's rank afterwards, which is 0 once fully removed or if it
+    /// wasn't stored.
+    pub fn remove_word_counted(&mut self, word: &str) -> i32 {
+        let word = self.fold(word).into_owned();
+        self.trie.decrement_record(&word)
+    }
+
+    /// Applies the configured transformations to a word that is about to be
+    /// inserted.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`String`) - Word as given by the caller.
+    ///
+    /// # Return value
+    ///
+    /// The word in the form it should be stored in, and with
+    /// `ignore_punctuation` or `preserve_case` set, the word as given, to be
+    /// counted as one of its surface forms.
+    fn prepare(&self, mut word: String) -> (String, Option<String>) {
+        let keep_surface = self.keeps_surfaces();
+        let mut surface = None;
+        match self.fold(&word) {
+            Cow::Owned(folded) => {
+                let original = std::mem::replace(&mut word, folded);
+                surface = Some(original).filter(|_| keep_surface);
+            }
+            // Unchanged spellings are counted too, so they can outvote others
+            Cow::Borrowed(_) if keep_surface => surface = Some(word.clone()),
+            Cow::Borrowed(_) => {}
+        }
+        #[cfg(feature = "stemming")]
+        if self.settings.stem {
+            word = Stemmer::create(Algorithm::English).stem(&word).into_owned();
+        }
+        (word, surface)
+    }
+
+    /// Prepares a word and hands it to `add` for inserting into the MWT, then
+    /// counts its surface form on the new node. Every insertion goes through
+    /// here, so surface forms live and die with the word itself.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`String`) - Word as given by the caller.
+    ///
+    /// `count` (`i32`) - How many uses of this spelling the insertion stands for.
+    ///
+    /// `add` (`F: FnOnce(&mut Mwt<T>, String) -> R`) - Inserts the prepared word.
+    ///
+    /// # Return value
+    ///
+    /// Whatever `add` returns.
+    fn insert_with<R, F: FnOnce(&mut Mwt<T>, String) -> R>(
+        &mut self,
+        word: String,
+        count: i32,
+        add: F,
+    ) -> R {
+        let (word, surface) = self.prepare(word);
+        let Some(surface) = surface else {
+            return add(&mut self.trie, word);
+        };
+        let key = word.clone();
+        let ret = add(&mut self.trie, word);
+        self.trie.record_surface(&key, surface, count);
+        ret
+    }
+
+    /// Folds the case of a word or prefix when the `Autocompleter` is case
+    /// insensitive or preserves case, collapses its whitespace when `normalize_whitespace` is
+    /// set, and strips punctuation and whitespace when `ignore_punctuation`
+    /// is set. Otherwise it's left alone.
+    ///
+    /// Both insertion (through `prepare`) and every query go through here, so
+    /// stored words and the strings they're looked up with are always folded
+    /// identically.
+    ///
+    /// # Arguments
+    ///
+    /// `text` (`&str`) - Word or prefix as given by the caller.
+    ///
+    /// # Return value
+    ///
+    /// The string to use as a key into the MWT.
+    fn fold<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        if self.settings.ignore_punctuation && text.contains(Autocompleter::is_ignored) {
+            text = Cow::Owned(
+                text.chars()
+                    .filter(|&ch| !Autocompleter::is_ignored(ch))
+                    .collect(),
+            );
+        }
+        if self.settings.normalize_whitespace && text.contains(char::is_whitespace) {
+            let mut collapsed = String::with_capacity(text.len());
+            let mut in_space = false;
+            for ch in text.chars() {
+                if ch.is_whitespace() {
+                    if !in_space {
+                        collapsed.push(' ');
+                    }
+                    in_space = true;
+                } else {
+                    collapsed.push(ch);
+                    in_space = false;
+                }
+            }
+            text = Cow::Owned(collapsed);
+        }
+        if self.settings.case_insensitive || self.settings.preserve_case {
+            text = Cow::Owned(text.to_lowercase());
+        }
+        text
+    }
+
+    /// Checks whether added words record the spellings they were added with,
+    /// because they're stored under a folded form that predictions shouldn't
+    /// show: with `ignore_punctuation` or `preserve_case`, or with
+    /// `dedup_folded` on top of `case_insensitive`.
+    fn keeps_surfaces(&self) -> bool {
+        self.settings.ignore_punctuation
+            || self.settings.preserve_case
+            || (self.settings.dedup_folded && self.settings.case_insensitive)
+    }
+
+    /// Checks whether `dedup_folded` has to merge casings at query time,
+    /// because words are stored with their case and so aren't merged when added.
+    fn merges_casings(&self) -> bool {
+        self.settings.dedup_folded
+            && !self.settings.case_insensitive
+            && !self.settings.preserve_case
+    }
+
+    /// Looks up the form a stored word is shown in, for reporting it
+    /// back from predictions when `ignore_punctuation` or `preserve_case`
+    /// changed it.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`String`) - Word as stored in the MWT.
+    ///
+    /// # Return value
+    ///
+    /// The surface form, or `word` itself if it was stored unchanged.
+    fn surface(&self, word: String) -> String {
+        if !self.keeps_surfaces() {
+            return word;
+        }
+        match self.trie.find_node(&word).and_then(|nd| nd.get_surface()) {
+            Some(surface) => surface.to_string(),
+            None => word,
+        }
+    }
+
+    /// Checks whether a word has been added to the `Autocompleter`.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`&str`) - Complete word to look for.
+    ///
+    /// # Return value
+    ///
+    /// `true` if `word` itself is stored, `false` otherwise (including when it
+    /// is only the beginning of a stored word).
+    pub fn contains(&self, word: &str) -> bool {
+        self.trie
+            .find_node(&self.fold(word))
+            .is_some_and(|nd| nd.get_end())
+    }
+
+    /// Looks up how many times a word has been added, i.e. its rank.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`&str`) - Complete word to look up.
+    ///
+    /// # Return value
+    ///
+    /// The word's rank, or `None` if `word` isn't stored.
+    pub fn rank_of(&self, word: &str) -> Option<i32> {
+        self.trie
+            .find_node(&self.fold(word))
+            .filter(|nd| nd.get_end())
+            .map(|nd| nd.get_rank())
+    }
+
+    /// Checks whether any stored word starts with the given prefix.
+    ///
+    /// Unlike `contains`, the prefix doesn't need to be a complete word, and
+    /// this is cheaper than checking `predict_completions` for emptiness since
+    /// no search below the prefix is needed.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Beginning of a word to look for.
+    ///
+    /// # Return value
+    ///
+    /// `true` if at least one stored word begins with `prefix`. An empty prefix
+    /// matches as soon as any word is stored.
+    pub fn contains_prefix(&self, prefix: &str) -> bool {
+        self.trie
+            .find_node(&self.fold(prefix))
+            .is_some_and(|nd| nd.get_end() || nd.child_count() > 0)
+    }
+
+    /// Counts the stored words that begin with `prefix`, including the prefix
+    /// itself if it is a word. Every node keeps a count of the words below it,
+    /// so this only walks the prefix rather than searching its subtree.
+    ///
+    /// Unlike the prediction methods, there is no minimum prefix length, and
+    /// `exclude_exact_match` doesn't apply.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Beginning of the words to count.
+    ///
+    /// # Return value
+    ///
+    /// Number of stored words beginning with `prefix`. An empty prefix counts
+    /// every word.
+    pub fn prefix_count(&self, prefix: &str) -> usize {
+        self.trie
+            .find_node(&self.fold(prefix))
+            .map_or(0, |nd| nd.get_subtree_word_count())
+    }
+
+    /// Checks whether any stored word is within `max_distance` edits
+    /// (insertions, deletions or substitutions) of `word`.
+    ///
+    /// The search stops at the first match found, so this is cheaper than
+    /// `suggest_correction` when only a yes or no is needed. A distance of 0
+    /// behaves like `contains`.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`&str`) - Word to look for.
+    ///
+    /// `max_distance` (`usize`) - Largest number of edits still counted as a match.
+    ///
+    /// # Return value
+    ///
+    /// `true` if some stored word is close enough to `word`.
+    pub fn contains_fuzzy(&self, word: &str, max_distance: usize) -> bool {
+        let target: Vec<char> = self.fold(word).chars().collect();
+        fuzzy::visit_within(
+            self.trie.get_root(),
+            &target,
+            max_distance,
+            &mut |nd, _, _| nd.get_end(),
+        )
+    }
+
+    /// Suggests the closest stored word for a word that isn't in the
+    /// `Autocompleter`, in the manner of a spell checker's "did you mean".
+    ///
+    /// Candidates are any stored words within 2 edits (insertions, deletions
+    /// or substitutions) of `word`. The one with the fewest edits wins, with
+    /// ties going to the more frequent word and then the alphabetically first.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`&str`) - Possibly misspelled word.
+    ///
+    /// # Return value
+    ///
+    /// The suggested correction, or `None` if `word` is already stored (so
+    /// needs no correction) or no stored word is close enough.
+    pub fn suggest_correction(&self, word: &str) -> Option<String> {
+        if self.contains(word) {
+            return None;
+        }
+
+        let target: Vec<char> = self.fold(word).chars().collect();
+        let mut best: Option<(usize, Reverse<i32>, String)> = None;
+        fuzzy::visit_within(
+            self.trie.get_root(),
+            &target,
+            MAX_CORRECTION_DISTANCE,
+            &mut |nd, word, distance| {
+                if nd.get_end() {
+                    let better = match &best {
+                        None => true,
+                        Some((best_distance, best_rank, best_word)) => {
+                            (distance, Reverse(nd.get_rank()), word)
+                                < (*best_distance, *best_rank, best_word.as_str())
+                        }
+                    };
+                    if better {
+                        best = Some((distance, Reverse(nd.get_rank()), word.to_string()));
+                    }
+                }
+                false
+            },
+        );
+        best.map(|(_, _, word)| self.surface(word))
+    }
+
+    /// Runs a prediction check like `predict_completions`, but also offers
+    /// completions of stored prefixes within `max_distance` edits
+    /// (insertions, deletions or substitutions) of `prefix`, so a typo early
+    /// in the word doesn't hide every suggestion.
+    ///
+    /// Completions of closer prefixes come first, with the configured
+    /// `SortOrder` deciding between equally close ones. A large distance on a
+    /// short prefix can match most of the dictionary, so keep it small.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Possibly misspelled word to search for.
+    ///
+    /// `max_distance` (`usize`) - Largest number of edits allowed between `prefix` and the
+    /// start of a completion.
+    ///
+    /// # Return value
+    ///
+    /// Up to 10 completions, closest first.
+    #[must_use]
+    pub fn predict_completions_fuzzy(&self, prefix: &str, max_distance: usize) -> Vec<String> {
+        if !self.long_enough(prefix) {
+            return Vec::new();
+        }
+
+        // Closest distance any matching prefix of each word was found at
+        let target: Vec<char> = self.fold(prefix).chars().collect();
+        let mut closest: HashMap<String, (usize, &MwtNode<T>)> = HashMap::new();
+        fuzzy::visit_within(
+            self.trie.get_root(),
+            &target,
+            max_distance,
+            &mut |nd, path, distance| {
+                Mwt::for_each_word(nd, path, false, |word, below| match closest.get_mut(word) {
+                    Some(entry) => entry.0 = entry.0.min(distance),
+                    None => {
+                        closest.insert(word.to_string(), (distance, below));
+                    }
+                });
+                false
+            },
+        );
+
+        let mut dfs_results: Vec<ScoredWord> = closest
+            .iter()
+            .map(|(word, (_, nd))| ScoredWord::from_node(nd, word))
+            .collect();
+        self.sort_results(&mut dfs_results);
+        // Stable, so the configured order is kept within each distance
+        dfs_results.sort_by_key(|r| closest[r.data.as_str()].0);
+        dfs_results
+            .into_iter()
+            .take(ELEMENTS_TO_RETURN)
+            .map(|r| self.surface(r.data))
+            .collect()
+    }
+
+    /// Runs a prediction check like `predict_completions_n`, but when no
+    /// stored word begins with `prefix`, corrects the prefix first and
+    /// completes that instead. For example with "receive" and "receiving"
+    /// stored, "recie" is corrected to "rece" and both words are suggested.
+    ///
+    /// The correction is the stored prefix the fewest edits (insertions,
+    /// deletions or substitutions) away, up to `max_distance`. When several
+    /// are equally close, their completions are ranked together. A transposed
+    /// pair of letters counts as 2 edits.
+    ///
+    /// If `prefix` is present, this costs the same as `predict_completions_n`.
+    /// Otherwise it runs the same Levenshtein search as
+    /// `predict_completions_fuzzy`, which visits every node within
+    /// `max_distance` edits of some alignment of `prefix`. That grows quickly
+    /// with `max_distance` and the size of the alphabet, so keep it at 1 or 2.
+    /// The corrected branches are then searched in full, with no pruning.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Possibly misspelled word to search for.
+    ///
+    /// `max_distance` (`usize`) - Largest number of edits allowed to correct `prefix`.
+    ///
+    /// `limit` (`usize`) - Maximum number of completions to return.
+    ///
+    /// # Return value
+    ///
+    /// Up to `limit` completions of `prefix`, or of its closest correction if
+    /// it has none. Empty if no stored prefix is close enough.
+    #[must_use]
+    pub fn predict_completions_autocorrect(
+        &self,
+        prefix: &str,
+        max_distance: usize,
+        limit: usize,
+    ) -> Vec<String> {
+        if !self.long_enough(prefix) {
+            return Vec::new();
+        }
+        if self.contains_prefix(prefix) {
+            return self.predict_completions_n(prefix, limit);
+        }
+
+        // Every node at the smallest distance found so far. The root is left
+        // out, since "correcting" to the empty prefix would suggest anything.
+        let root = self.trie.get_root();
+        let target: Vec<char> = self.fold(prefix).chars().collect();
+        let mut closest = usize::MAX;
+        let mut nearest: Vec<(&MwtNode<T>, String)> = Vec::new();
+        fuzzy::visit_within(root, &target, max_distance, &mut |nd, path, distance| {
+            if !std::ptr::eq(nd, root) && distance <= closest {
+                if distance < closest {
+                    closest = distance;
+                    nearest.clear();
+                }
+                nearest.push((nd, path.to_string()));
+            }
+            false
+        });
+
+        // Nearest prefixes can be nested, e.g. "rece" and "recei"
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut dfs_results: Vec<ScoredWord> = nearest
+            .into_iter()
+            .flat_map(|(nd, path)| self.candidates_below(nd, &path))
+            .filter(|r| seen.insert(r.data.clone()))
+            .collect();
+        self.sort_results(&mut dfs_results);
+        dfs_results
+            .into_iter()
+            .take(limit)
+            .map(|r| self.surface(r.data))
+            .collect()
+    }
+
+    /// Runs a prediction check for a given prefixed String.
+    ///
+    /// This prediction check is accomplished by traversing the MWT as
+    /// far down as possible, then it runs a depth-first search to traverse
+    /// the rest of the MWT to grab finished words.
+    ///
+    /// From there, the autocompleter returns the top 10 words according to its
+    /// `SortOrder`. By default that is frequency first, with alphabetical order
+    /// breaking ties.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// # Return value
+    ///
+    /// This function returns a vector of strings that corresponds to the predictions.
+    #[must_use]
+    pub fn predict_completions(&self, prefix: &str) -> Vec<String> {
+        self.predict_completions_n(prefix, ELEMENTS_TO_RETURN)
+    }
+
+    /// Runs a prediction check like `predict_completions`, but says why when
+    /// there are no completions, so a UI can tell "keep typing" apart from
+    /// "nothing matches".
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// # Return value
+    ///
+    /// Up to 10 completions, which is never empty, or
+    /// `PredictError::PrefixTooShort` if the prefix is below the minimum
+    /// length, or `PredictError::NoMatches` if nothing completes it.
+    pub fn predict_completions_result(&self, prefix: &str) -> Result<Vec<String>, PredictError> {
+        if !self.long_enough(prefix) {
+            return Err(PredictError::PrefixTooShort {
+                min_len: self.settings.min_prefix_len.unwrap_or(MIN_LEN),
+            });
+        }
+        let results = self.predict_completions(prefix);
+        if results.is_empty() {
+            return Err(PredictError::NoMatches);
+        }
+        Ok(results)
+    }
+
+    /// Runs a prediction check like `predict_completions`, and pairs each
+    /// completion with the byte length of the part that matched `prefix`.
+    ///
+    /// UIs can use this to highlight the matched part of each suggestion,
+    /// e.g. `&word[..len]` in bold.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// # Return value
+    ///
+    /// A vector of `(completion, matched_len)` pairs in prediction order.
+    #[must_use]
+    pub fn predict_completions_highlighted(&self, prefix: &str) -> Vec<(String, usize)> {
+        let key = self.fold(prefix);
+        self.predict_completions(prefix)
+            .into_iter()
+            .map(|word| {
+                let len = self.matched_len(&key, &word);
+                (word, len)
+            })
+            .collect()
+    }
+
+    /// Runs a prediction check like `predict_completions`, but returns up to
+    /// `limit` completions instead of the default 10.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// `limit` (`usize`) - Maximum number of completions to return.
+    ///
+    /// # Return value
+    ///
+    /// This function returns a vector of strings that corresponds to the predictions.
+    #[must_use]
+    pub fn predict_completions_n(&self, prefix: &str, limit: usize) -> Vec<String> {
+        self.top_candidates(prefix, limit)
+            .into_iter()
+            .map(|r| self.surface(r.data))
+            .collect()
+    }
+
+    /// Checks whether `predict_completions` would return anything for a
+    /// prefix, without collecting any completions, e.g. to decide whether to
+    /// show a suggestion box at all. Every node knows how many words are
+    /// stored below it, so this only costs the walk down to the prefix. The
+    /// exception is `graphemes` mode, which has to search the subtree.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// # Return value
+    ///
+    /// `true` if at least one completion exists.
+    #[must_use]
+    pub fn has_completions(&self, prefix: &str) -> bool {
+        if !self.long_enough(prefix) {
+            return false;
+        }
+        match self.trie.find_node(&self.fold(prefix)) {
+            None => false,
+            Some(_) if self.grapheme_mode() => !self.candidates(prefix).is_empty(),
+            Some(nd) => {
+                nd.get_subtree_word_count()
+                    > usize::from(self.settings.exclude_exact_match && nd.get_end())
+            }
+        }
+    }
+
+    /// Runs a prediction check like `predict_completions_n`, and reports
+    /// alongside the completions whether the prefix was found at all and
+    /// whether more completions existed than `limit` allowed, so a UI can
+    /// show something like "and 12 more".
+    ///
+    /// Finding out whether the results were cut short costs nothing extra,
+    /// since every node keeps a count of the words below it. In `graphemes`
+    /// mode the completions are counted by a full search instead.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// `limit` (`usize`) - Maximum number of completions to return.
+    ///
+    /// # Return value
+    ///
+    /// A `PredictionResult` holding the completions. A prefix shorter than
+    /// the minimum length gets no completions, but `found` still says whether
+    /// it is present.
+    #[must_use]
+    pub fn predict_completions_info(&self, prefix: &str, limit: usize) -> PredictionResult {
+        let key = self.fold(prefix).into_owned();
+        let results = self.predict_completions_n(prefix, limit);
+        let node = self.trie.find_node(&key);
+        let found = node.is_some_and(|nd| nd.get_end() || nd.child_count() > 0);
+        let available = match node {
+            Some(_) if !self.long_enough(prefix) => 0,
+            Some(_) if self.grapheme_mode() => self.candidates(prefix).len(),
+            Some(nd) => {
+                nd.get_subtree_word_count()
+                    - usize::from(self.settings.exclude_exact_match && nd.get_end())
+            }
+            None => 0,
+        };
+        PredictionResult {
+            prefix: key,
+            is_truncated: available > results.len(),
+            results,
+            found,
+        }
+    }
+
+    /// Runs a prediction check like `predict_completions_n`, but returns the
+    /// completions borrowed straight from the `Autocompleter` instead of as
+    /// new `String`s, avoiding an allocation per result. Useful when results
+    /// are serialized right away and don't need to be owned.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// `limit` (`usize`) - Maximum number of completions to return.
+    ///
+    /// # Return value
+    ///
+    /// The same completions as `predict_completions_n`, borrowed for as long
+    /// as the `Autocompleter` is. With `store_words(false)` or without the
+    /// `store-words` feature there is nothing to borrow from, so the
+    /// completions are owned instead.
+    #[must_use]
+    pub fn predict_completions_ref(&self, prefix: &str, limit: usize) -> Vec<Cow<'_, str>> {
+        if self.merges_casings() {
+            // Merged completions aren't in the MWT, so there is nothing to borrow
+            return self
+                .predict_completions_n(prefix, limit)
+                .into_iter()
+                .map(Cow::Owned)
+                .collect();
+        }
+        let results = self.top_candidates_as::<BorrowedWord<'_, T>>(prefix, limit);
+        self.mark_used(&results);
+        results
+            .into_iter()
+            .map(|r| match r.node.get_surface() {
+                Some(surface) => Cow::Borrowed(surface),
+                None => r.word,
+            })
+            .collect()
+    }
+
+    /// Runs `predict_completions_n` for each of several prefixes.
+    ///
+    /// # Arguments
+    ///
+    /// `prefixes` (`I: IntoIterator<Item = &str>`) - Prefixes to complete.
+    ///
+    /// `limit` (`usize`) - Maximum number of completions per prefix.
+    ///
+    /// # Return value
+    ///
+    /// One vector of completions per prefix, in the same order as `prefixes`.
+    /// A prefix with no completions gets an empty vector.
+    #[must_use]
+    pub fn predict_batch<'a, I: IntoIterator<Item = &'a str>>(
+        &self,
+        prefixes: I,
+        limit: usize,
+    ) -> Vec<Vec<String>> {
+        prefixes
+            .into_iter()
+            .map(|prefix| self.predict_completions_n(prefix, limit))
+            .collect()
+    }
+
+    /// Predicts completions for several prefixes at once and ranks them
+    /// together, e.g. to complete a word and its synonyms in one list.
+    ///
+    /// Each word appears only once. A word matching more than one prefix,
+    /// which can only happen when one prefix extends another (such as "ca"
+    /// and "car"), is ranked by the sum of its rank from every match.
+    ///
+    /// # Arguments
+    ///
+    /// `prefixes` (`&[&str]`) - Prefixes to complete.
+    ///
+    /// `limit` (`usize`) - Maximum number of completions to return overall.
+    ///
+    /// # Return value
+    ///
+    /// Up to `limit` completions drawn from all of the prefixes, in the
+    /// configured `SortOrder`.
+    #[must_use]
+    pub fn predict_union(&self, prefixes: &[&str], limit: usize) -> Vec<String> {
+        let mut merged: HashMap<String, ScoredWord> = HashMap::new();
+        for prefix in prefixes {
+            for res in self.candidates(prefix) {
+                match merged.get_mut(&res.data) {
+                    Some(existing) => {
+                        existing.count = existing.count.saturating_add(res.count);
+                        existing.recency += res.recency;
+                    }
+                    None => {
+                        merged.insert(res.data.clone(), res);
+                    }
+                }
+            }
+        }
+
+        let mut results: Vec<ScoredWord> = merged.into_values().collect();
+        self.sort_results(&mut results);
+        results
+            .into_iter()
+            .take(limit)
+            .map(|r| self.surface(r.data))
+            .collect()
+    }
+
+    /// Runs a prediction check like `predict_completions_n`, but returns each
+    /// completion together with its rank.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// `limit` (`usize`) - Maximum number of completions to return.
+    ///
+    /// # Return value
+    ///
+    /// Up to `limit` `ScoredWord`s, in the configured `SortOrder`.
+    #[must_use]
+    pub fn predict_scored(&self, prefix: &str, limit: usize) -> Vec<ScoredWord> {
+        let mut results = self.top_candidates(prefix, limit);
+        for r in &mut results {
+            r.data = self.surface(std::mem::take(&mut r.data));
+        }
+        results
+    }
+
+    /// Runs a prediction check like `predict_completions_n`, but pairs each
+    /// completion with its rank, for output that shows how popular each one
+    /// is.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// `limit` (`usize`) - Maximum number of completions to return.
+    ///
+    /// # Return value
+    ///
+    /// Up to `limit` `(word, rank)` pairs, in the configured `SortOrder`.
+    #[must_use]
+    pub fn predict_completions_with_counts(
+        &self,
+        prefix: &str,
+        limit: usize,
+    ) -> Vec<(String, i32)> {
+        self.predict_scored(prefix, limit)
+            .into_iter()
+            .map(|r| (r.data, r.count))
+            .collect()
+    }
+
+    /// Runs a prediction check like `predict_completions`, but also returns
+    /// each completion's rank and how many characters it extends past the
+    /// prefix, to help diagnose why long completions show up or to pick a
+    /// `max_word_len`.
+    ///
+    /// The same top 10 as `predict_completions` are returned, so the output
+    /// explains exactly what a user would see. Use `predict_scored` with a
+    /// larger limit to look further down the ranking.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// # Return value
+    ///
+    /// `(word, rank, suffix_len)` triples in the configured `SortOrder`, where
+    /// `suffix_len` is the word's length minus the prefix's, in characters.
+    #[must_use]
+    pub fn predict_verbose(&self, prefix: &str) -> Vec<(String, i32, usize)> {
+        let prefix_len = self.fold(prefix).chars().count();
+        self.predict_scored(prefix, ELEMENTS_TO_RETURN)
+            .into_iter()
+            .map(|r| {
+                let suffix_len = r.data.chars().count().saturating_sub(prefix_len);
+                (r.data, r.count, suffix_len)
+            })
+            .collect()
+    }
+
+    /// Runs a prediction check like `predict_completions`, but only returns
+    /// phrases, i.e. entries containing a space, so "mach" can suggest
+    /// "machine learning" without also suggesting "machine".
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Beginning of the phrase to search for.
+    ///
+    /// # Return value
+    ///
+    /// Up to 10 phrases, in the configured `SortOrder`.
+    #[must_use]
+    pub fn predict_phrases(&self, prefix: &str) -> Vec<String> {
+        let mut dfs_results = self.candidates(prefix);
+        dfs_results.retain(|r| r.data.contains(' '));
+        self.sort_results(&mut dfs_results);
+        dfs_results
+            .into_iter()
+            .take(ELEMENTS_TO_RETURN)
+            .map(|r| self.surface(r.data))
+            .collect()
+    }
+
+    /// Collects every stored word beginning with `prefix`, ranked like
+    /// `predict_completions` but with no limit applied.
+    ///
+    /// For short prefixes this can be a large share of the dictionary, so
+    /// prefer `predict_completions_n` when only the top few are needed.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// # Return value
+    ///
+    /// Every completion, in the configured `SortOrder`.
+    pub fn words_with_prefix(&self, prefix: &str) -> Vec<String> {
+        let mut dfs_results = self.candidates(prefix);
+        self.sort_results(&mut dfs_results);
+        dfs_results
+            .into_iter()
+            .map(|r| self.surface(r.data))
+            .collect()
+    }
+
+    /// Starts a typing session, for completing a word as it's typed one
+    /// character at a time. See `Session`.
+    ///
+    /// # Return value
+    ///
+    /// A `Session` with an empty prefix.
+    pub fn session(&self) -> Session<'_, T> {
+        Session::new(self)
+    }
+
+    /// Runs a prediction check like `predict_completions`, but yields the
+    /// completions one at a time through an iterator with no limit applied.
+    ///
+    /// Ranking needs the full set of candidates, so the search and sort happen
+    /// once up front; the iterator then hands out the already-ranked words. The
+    /// order is identical to `predict_completions`, so `.take(10)` produces the
+    /// same words.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// # Return value
+    ///
+    /// An iterator over every completion of `prefix`, best first.
+    pub fn predict_iter(&self, prefix: &str) -> impl Iterator<Item = String> {
+        let mut dfs_results = self.candidates(prefix);
+        self.sort_results(&mut dfs_results);
+        // Look up surface forms now, so the iterator doesn't borrow `self`
+        for r in &mut dfs_results {
+            r.data = self.surface(std::mem::take(&mut r.data));
+        }
+        dfs_results.into_iter().map(|r| r.data)
+    }
+
+    /// Runs a prediction check like `predict_scored`, but yields every
+    /// completion with its rank one at a time, best first, so a consumer can
+    /// stop early.
+    ///
+    /// The completions are collected up front and, with the default ranking,
+    /// put in a heap that the iterator pops one word from per step, so
+    /// `.take(k)` only pays for ranking the `k` words it takes. With any other
+    /// `SortOrder`, `TieBreak`, recency ranking, float scores or a ranker, the
+    /// completions are sorted up front instead. Either way the order is the
+    /// same as `predict_scored` with no limit.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// # Return value
+    ///
+    /// An iterator over `(word, rank)` pairs for every completion of `prefix`.
+    pub fn predict_ranked_iter(&self, prefix: &str) -> impl Iterator<Item = (String, i32)> + '_ {
+        let mut results = self.candidates(prefix);
+        let key = self.fold(prefix);
+        let mut exact = None;
+        if self.settings.boost_exact_match && !self.settings.exclude_exact_match {
+            if let Some(i) = results.iter().position(|r| r.data == *key) {
+                exact = Some(results.swap_remove(i));
+            }
+        }
+
+        let mut heap = BinaryHeap::new();
+        if self.plain_ranking() {
+            // `ScoredWord` orders best first, so the heap's top is the worst
+            // word unless the order is reversed
+            heap = results.drain(..).map(Reverse).collect();
+        } else {
+            self.sort_results(&mut results);
+        }
+
+        let mut sorted = results.into_iter();
+        std::iter::from_fn(move || {
+            let r = exact
+                .take()
+                .or_else(|| heap.pop().map(|Reverse(r)| r))
+                .or_else(|| sorted.next())?;
+            Some((self.surface(r.data), r.count))
+        })
+    }
+
+    /// Runs a prediction check like `predict_completions_n`, but leaves out
+    /// any word in `exclude` before truncating to `limit`, so the next best
+    /// words move up to fill the gap. Useful for not suggesting a word that
+    /// was just accepted, without touching the stored ranks.
+    ///
+    /// Words are compared exactly as predictions return them, i.e. after case
+    /// folding when the `Autocompleter` is case-insensitive.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// `exclude` (`&HashSet<String>`) - Words to leave out.
+    ///
+    /// `limit` (`usize`) - Maximum number of completions to return.
+    ///
+    /// # Return value
+    ///
+    /// Up to `limit` completions not in `exclude`, in the configured `SortOrder`.
+    #[must_use]
+    pub fn predict_completions_excluding(
+        &self,
+        prefix: &str,
+        exclude: &HashSet<String>,
+        limit: usize,
+    ) -> Vec<String> {
+        // At most every excluded word is among the best, so asking for that
+        // many extra is always enough
+        self.top_candidates(prefix, limit.saturating_add(exclude.len()))
+            .into_iter()
+            .map(|r| self.surface(r.data))
+            .filter(|word| !exclude.contains(word))
+            .take(limit)
+            .collect()
+    }
+
+    /// Runs a prediction check like `predict_completions_n`, but leaves out
+    /// words whose rank is below `min_count` before ranking and truncating.
+    ///
+    /// A `min_count` of 1 or less behaves exactly like `predict_completions_n`.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// `min_count` (`i32`) - Smallest rank a completion needs to be returned.
+    ///
+    /// `limit` (`usize`) - Maximum number of completions to return.
+    ///
+    /// # Return value
+    ///
+    /// Up to `limit` sufficiently frequent completions, in the configured `SortOrder`.
+    #[must_use]
+    pub fn predict_completions_min_freq(
+        &self,
+        prefix: &str,
+        min_count: i32,
+        limit: usize,
+    ) -> Vec<String> {
+        let mut dfs_results = self.candidates(prefix);
+        dfs_results.retain(|r| r.count >= min_count);
+        self.sort_results(&mut dfs_results);
+        dfs_results
+            .into_iter()
+            .take(limit)
+            .map(|r| self.surface(r.data))
+            .collect()
+    }
+
+    /// Runs a prediction check like `predict_completions_n`, but skips any
+    /// completion longer than `max_word_len` characters.
+    ///
+    /// Branches of the MWT are abandoned as soon as they get too deep, so long
+    /// words below the prefix cost nothing to leave out. Lengths are of words
+    /// as stored, so with `ignore_punctuation` "U.S.A" counts as the 3
+    /// characters of "USA", and with `normalize_whitespace` a run of spaces
+    /// counts as one.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// `max_word_len` (`usize`) - Longest completion, in characters, to return.
+    ///
+    /// `limit` (`usize`) - Maximum number of completions to return.
+    ///
+    /// # Return value
+    ///
+    /// Up to `limit` short enough completions, in the configured `SortOrder`.
+    #[must_use]
+    pub fn predict_completions_max_len(
+        &self,
+        prefix: &str,
+        max_word_len: usize,
+        limit: usize,
+    ) -> Vec<String> {
+        if !self.long_enough(prefix) {
+            return Vec::new();
+        }
+        // Depths in the MWT count the characters of the folded key, which
+        // can be fewer than were typed
+        let prefix = self.fold(prefix);
+        let prefix_len = prefix.chars().count();
+        if prefix_len > max_word_len {
+            return Vec::new();
+        }
+
+        let mut dfs_results = Vec::new();
+        if let Some(nd) = self.trie.find_node(&prefix) {
+            Autocompleter::depth_limited_search(
+                nd,
+                &prefix,
+                prefix_len,
+                max_word_len,
+                &mut dfs_results,
+            );
+        }
+        if self.settings.exclude_exact_match {
+            dfs_results.retain(|r| r.data != *prefix);
+        }
+        self.sort_results(&mut dfs_results);
+        dfs_results
+            .into_iter()
+            .take(limit)
+            .map(|r| self.surface(r.data))
+            .collect()
+    }
+
+    /// Runs a prediction check like `predict_completions`, but only returns
+    /// the shortest completion down each branch below the prefix.
+    ///
+    /// Once a branch reaches a stored word, longer words beyond it are skipped,
+    /// so with "run", "runner" and "running" stored, predicting "ru" gives just
+    /// "run". The prefix's own word doesn't cut the search short: predicting
+    /// "run" gives "run", "runner" and "running". This gives more varied
+    /// suggestions for vocabularies with many nested words.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// # Return value
+    ///
+    /// Up to 10 completions, in the configured `SortOrder`.
+    #[must_use]
+    pub fn predict_shortest_completions(&self, prefix: &str) -> Vec<String> {
+        if !self.long_enough(prefix) {
+            return Vec::new();
+        }
+        let mut path = self.fold(prefix).into_owned();
+        let nd = match self.trie.find_node(&path) {
+            Some(nd) => nd,
+            None => return Vec::new(),
+        };
+
+        let mut dfs_results = Vec::new();
+        if nd.get_end() && !self.settings.exclude_exact_match {
+            dfs_results.push(ScoredWord::from_node(nd, &path));
+        }
+        for (ch, child) in nd.children_iter() {
+            path.push(ch);
+            Autocompleter::shortest_search(child, &path, &mut dfs_results);
+            path.pop();
+        }
+        self.sort_results(&mut dfs_results);
+        dfs_results
+            .into_iter()
+            .take(ELEMENTS_TO_RETURN)
+            .map(|r| self.surface(r.data))
+            .collect()
+    }
+
+    /// Runs a prediction check like `predict_completions_n`, but only keeps
+    /// completions that also match a regular expression, e.g. completions of
+    /// "pre" matching `ion$`.
+    ///
+    /// The regex is applied to each completion after the prefix search, so
+    /// the cost is linear in the number of words under the prefix rather
+    /// than the size of the dictionary.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// `pattern` (`&Regex`) - Expression each completion must match somewhere.
+    ///
+    /// `limit` (`usize`) - Maximum number of completions to return.
+    ///
+    /// # Return value
+    ///
+    /// Up to `limit` matching completions, in the configured `SortOrder`.
+    #[cfg(feature = "regex")]
+    #[must_use]
+    pub fn predict_matching(&self, prefix: &str, pattern: &Regex, limit: usize) -> Vec<String> {
+        let mut dfs_results = self.candidates(prefix);
+        dfs_results.retain(|r| pattern.is_match(&r.data));
+        self.sort_results(&mut dfs_results);
+        dfs_results
+            .into_iter()
+            .take(limit)
+            .map(|r| self.surface(r.data))
+            .collect()
+    }
+
+    /// Finds stored words containing the given text anywhere, e.g. "graph"
+    /// in "autograph" and "graphics".
+    ///
+    /// This is a fallback for when the start of the word isn't known. Every
+    /// stored word is visited and checked, so it costs O(n) in the size of the
+    /// dictionary, unlike the fast prefix path of `predict_completions`.
+    ///
+    /// # Arguments
+    ///
+    /// `needle` (`&str`) - Text to search for.
+    ///
+    /// `limit` (`usize`) - Maximum number of words to return.
+    ///
+    /// # Return value
+    ///
+    /// Up to `limit` matching words, ranked in the configured `SortOrder`.
+    pub fn search_substring(&self, needle: &str, limit: usize) -> Vec<String> {
+        let needle = self.fold(needle);
+        let mut matches: Vec<ScoredWord> = self
+            .depth_first_search(Some(self.trie.get_root()), "")
+            .into_iter()
+            .filter(|r| r.data.contains(&*needle))
+            .collect();
+        self.sort_results(&mut matches);
+        matches
+            .into_iter()
+            .take(limit)
+            .map(|r| self.surface(r.data))
+            .collect()
+    }
+
+    /// Finds stored words that end with the given suffix, e.g. "tion".
+    ///
+    /// The MWT is organized by prefix, so there is no shortcut for this: every
+    /// stored word is visited and checked. Expect it to cost O(n) in the size
+    /// of the dictionary, unlike the fast prefix path of `predict_completions`.
+    ///
+    /// # Arguments
+    ///
+    /// `suffix` (`&str`) - Ending to search for.
+    ///
+    /// `limit` (`usize`) - Maximum number of words to return.
+    ///
+    /// # Return value
+    ///
+    /// Up to `limit` matching words, ranked in the configured `SortOrder`.
+    #[must_use]
+    pub fn predict_suffix(&self, suffix: &str, limit: usize) -> Vec<String> {
+        let suffix = self.fold(suffix);
+        let mut matches: Vec<ScoredWord> = self
+            .depth_first_search(Some(self.trie.get_root()), "")
+            .into_iter()
+            .filter(|r| r.data.ends_with(&*suffix))
+            .collect();
+
+        self.sort_results(&mut matches);
+        matches
+            .into_iter()
+            .take(limit)
+            .map(|r| self.surface(r.data))
+            .collect()
+    }
+
+    /// Runs a prediction check like `predict_completions`, but pairs each
+    /// completion with its probability among all words matching the prefix.
+    ///
+    /// The probability of a word is its rank divided by the sum of the ranks of
+    /// *every* word starting with `prefix`, not just the ones returned, so the
+    /// values of a truncated result set will generally sum to less than 1.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// `limit` (`usize`) - Maximum number of completions to return.
+    ///
+    /// # Return value
+    ///
+    /// A vector of `(word, probability)` pairs in the configured `SortOrder`.
+    /// Empty if nothing matches the prefix.
+    #[must_use]
+    pub fn predict_completions_with_probability(
+        &self,
+        prefix: &str,
+        limit: usize,
+    ) -> Vec<(String, f64)> {
+        let mut dfs_results = self.candidates(prefix);
+
+        // Guard against dividing by zero when there are no matches at all.
+        let total: i64 = dfs_results.iter().map(|r| r.count as i64).sum();
+        if total == 0 {
+            return Vec::new();
+        }
+
+        self.sort_results(&mut dfs_results);
+        dfs_results
+            .into_iter()
+            .take(limit)
+            .map(|r| {
+                let probability = r.count as f64 / total as f64;
+                (self.surface(r.data), probability)
+            })
+            .collect()
+    }
+
+    /// Runs a prediction check like `predict_completions_n`, but pairs each
+    /// completion with the payload it was given by `add_word_with_payload`.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// `limit` (`usize`) - Maximum number of completions to return.
+    ///
+    /// # Return value
+    ///
+    /// `(word, payload)` pairs in the configured `SortOrder`. Words added
+    /// without a payload have `None`.
+    #[must_use]
+    pub fn predict_with_payloads(&self, prefix: &str, limit: usize) -> Vec<(String, Option<&T>)> {
+        self.top_candidates(prefix, limit)
+            .into_iter()
+            .map(|r| {
+                let payload = self.trie.find_node(&r.data).and_then(|nd| nd.get_payload());
+                (self.surface(r.data), payload)
+            })
+            .collect()
+    }
+
+    /// Runs a prediction check like `predict_completions_n`, but pairs each
+    /// completion with the identifiers of every source it was added from with
+    /// `add_word_with_source`.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// `limit` (`usize`) - Maximum number of completions to return.
+    ///
+    /// # Return value
+    ///
+    /// `(word, source_ids)` pairs in the configured `SortOrder`, with the
+    /// identifiers in ascending order. Words added without a source, or while
+    /// `track_sources` is off, have no identifiers.
+    #[must_use]
+    pub fn predict_with_sources(&self, prefix: &str, limit: usize) -> Vec<(String, Vec<u64>)> {
+        self.top_candidates(prefix, limit)
+            .into_iter()
+            .map(|r| {
+                let sources = self
+                    .trie
+                    .find_node(&r.data)
+                    .map_or_else(Vec::new, |nd| nd.get_sources().collect());
+                (self.surface(r.data), sources)
+            })
+            .collect()
+    }
+
+    /// Runs a prediction check like `predict_completions`, but pairs each
+    /// completion with its share of all usage under the prefix, e.g. "within
+    /// 'th', 42% of insertions were 'the'".
+    ///
+    /// The share is the word's rank divided by the sum of the ranks of every
+    /// word in the prefix's subtree, clamped to `[0, 1]`. Unlike
+    /// `predict_completions_with_probability`, the total always includes the
+    /// prefix's own word, even when `exclude_exact_match` leaves it out of
+    /// the results.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// `limit` (`usize`) - Maximum number of completions to return.
+    ///
+    /// # Return value
+    ///
+    /// A vector of `(word, confidence)` pairs in the configured `SortOrder`.
+    /// Empty if nothing matches the prefix.
+    #[must_use]
+    pub fn predict_with_confidence(&self, prefix: &str, limit: usize) -> Vec<(String, f64)> {
+        let key = self.fold(prefix);
+        let total: i64 = match self.trie.find_node(&key) {
+            Some(nd) => self
+                .depth_first_search(Some(nd), &key)
+                .iter()
+                .map(|r| r.count as i64)
+                .sum(),
+            None => 0,
+        };
+        // Guard against dividing by zero for an empty subtree.
+        if total == 0 {
+            return Vec::new();
+        }
+
+        let mut dfs_results = self.candidates(prefix);
+        self.sort_results(&mut dfs_results);
+        dfs_results
+            .into_iter()
+            .take(limit)
+            .map(|r| {
+                let confidence = (r.count as f64 / total as f64).clamp(0.0, 1.0);
+                (self.surface(r.data), confidence)
+            })
+            .collect()
+    }
+
+    /// Iterates over every stored word, borrowed from the `Autocompleter`.
+    /// With `store_words(false)` there is nothing to borrow from, so each word
+    /// is rebuilt as an owned string instead.
+    ///
+    /// # Return value
+    ///
+    /// An iterator over the stored words, in unspecified order.
+    pub fn words(&self) -> impl Iterator<Item = Cow<'_, str>> {
+        // Each entry holds the length of its parent's path and the character
+        // leading to it, like `Mwt::for_each_word`, but the walk is lazy
+        let mut path = String::new();
+        let mut stack: Vec<(usize, Option<char>, &MwtNode<T>)> =
+            vec![(0, None, self.trie.get_root())];
+        std::iter::from_fn(move || {
+            while let Some((len, ch, nd)) = stack.pop() {
+                path.truncate(len);
+                path.extend(ch);
+                let len = path.len();
+                stack.extend(nd.children_iter().map(|(ch, child)| (len, Some(ch), child)));
+                if nd.get_end() {
+                    return Some(Autocompleter::word_of(nd, &path));
+                }
+            }
+            None
+        })
+    }
+
+    /// Lists the words stored in this `Autocompleter` but not in `other`,
+    /// e.g. to find the vocabulary one corpus has that another lacks. Words
+    /// are compared exactly, and ranks are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// `other` (`&Autocompleter`) - Completer whose words to leave out.
+    ///
+    /// # Return value
+    ///
+    /// The words only found in `self`, in alphabetical order.
+    pub fn difference(&self, other: &Autocompleter<T>) -> Vec<String> {
+        let mut words: Vec<String> = self
+            .words()
+            .filter(|word| !other.contains(word))
+            .map(String::from)
+            .collect();
+        words.sort_unstable();
+        words
+    }
+
+    /// Lists the words stored in both this `Autocompleter` and `other`. Words
+    /// are compared exactly, and ranks are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// `other` (`&Autocompleter`) - Completer to compare against.
+    ///
+    /// # Return value
+    ///
+    /// The words found in both, in alphabetical order.
+    pub fn intersection(&self, other: &Autocompleter<T>) -> Vec<String> {
+        let mut words: Vec<String> = self
+            .words()
+            .filter(|word| other.contains(word))
+            .map(String::from)
+            .collect();
+        words.sort_unstable();
+        words
+    }
+
+    /// Compares the ranks of every word between this `Autocompleter` and
+    /// `other`, e.g. to find terms that became more or less popular between
+    /// two corpora. Words are compared exactly as stored.
+    ///
+    /// # Arguments
+    ///
+    /// `other` (`&Autocompleter`) - Completer to compare against.
+    ///
+    /// # Return value
+    ///
+    /// A `(word, rank in self, rank in other)` triple for every word stored
+    /// in either, with 0 for the side that doesn't have it. The biggest
+    /// changes in rank come first, in either direction, and equal changes
+    /// are in alphabetical order.
+    pub fn diff_ranks(&self, other: &Autocompleter<T>) -> Vec<(String, i32, i32)> {
+        let mut ranks: HashMap<String, (i32, i32)> = HashMap::new();
+        for (word, rank) in self.to_vec() {
+            ranks.entry(word).or_default().0 = rank;
+        }
+        for (word, rank) in other.to_vec() {
+            ranks.entry(word).or_default().1 = rank;
+        }
+
+        let mut diff: Vec<(String, i32, i32)> = ranks
+            .into_iter()
+            .map(|(word, (ours, theirs))| (word, ours, theirs))
+            .collect();
+        diff.sort_unstable_by(|a, b| {
+            let change =
+                |&(_, ours, theirs): &(String, i32, i32)| (ours as i64 - theirs as i64).abs();
+            change(b).cmp(&change(a)).then_with(|| a.0.cmp(&b.0))
+        });
+        diff
+    }
+
+    /// Collects every stored word along with its rank.
+    ///
+    /// # Return value
+    ///
+    /// A vector of `(word, rank)` pairs, one per stored word. The order is
+    /// unspecified, so sort it if a particular order is needed.
+    pub fn to_vec(&self) -> Vec<(String, i32)> {
+        self.depth_first_search(Some(self.trie.get_root()), "")
+            .into_iter()
+            .map(|r| (r.data, r.count))
+            .collect()
+    }
+
+    /// Writes every stored word to a file, most popular first.
+    ///
+    /// Each line holds a word and its rank separated by a tab (`word\tcount`).
+    /// Words are ordered by frequency descending, then alphabetically, no matter
+    /// which `SortOrder` the `Autocompleter` uses for predictions.
+    ///
+    /// # Arguments
+    ///
+    /// `path` (`&str`) - Name of the file to write. It is created or truncated.
+    ///
+    /// # Return value
+    ///
+    /// Either nothing, or an error string if the file could not be written.
+    pub fn export_sorted(&self, path: &str) -> Result<(), String> {
+        Self::write_ranked(path, self.words_by_popularity())
+    }
+
+    /// Writes only the most popular words to a file, e.g. the top 10% of the
+    /// dictionary by rank, to prune the long tail before shipping it. The file
+    /// has the same format and order as `export_sorted`.
+    ///
+    /// A word makes the cut when the words with a strictly higher rank are
+    /// fewer than `percentile` percent of the dictionary, so words tied at the
+    /// boundary are all kept and the file may hold a few more than asked for.
+    ///
+    /// # Arguments
+    ///
+    /// `percentile` (`f64`) - Share of the dictionary to keep, as a percentage
+    /// greater than 0 and at most 100.
+    ///
+    /// `path` (`&str`) - Name of the file to write. It is created or truncated.
+    ///
+    /// # Return value
+    ///
+    /// Either nothing, or an error string if `percentile` is out of range or
+    /// the file could not be written.
+    pub fn export_top_percentile(&self, percentile: f64, path: &str) -> Result<(), String> {
+        if !(percentile > 0.0 && percentile <= 100.0) {
+            return Err(format!(
+                "Percentile must be greater than 0 and at most 100, got {percentile}"
+            ));
+        }
+
+        let all_words = self.words_by_popularity();
+        let cutoff = percentile / 100.0 * all_words.len() as f64;
+        let mut higher = 0;
+        let mut kept = 0;
+        for (i, item) in all_words.iter().enumerate() {
+            if i > 0 && item.count != all_words[i - 1].count {
+                higher = i;
+            }
+            if higher as f64 >= cutoff {
+                break;
+            }
+            kept = i + 1;
+        }
+
+        Self::write_ranked(path, all_words.into_iter().take(kept))
+    }
+
+    /// Shared helper for `export_sorted` and `export_top_percentile`. Writes
+    /// one `word\tcount` line per word, in the order given.
+    ///
+    /// # Arguments
+    ///
+    /// `path` (`&str`) - Name of the file to write. It is created or truncated.
+    ///
+    /// `words` (`impl IntoIterator<Item = ScoredWord>`) - Words to write.
+    ///
+    /// # Return value
+    ///
+    /// Either nothing, or an error string if the file could not be written.
+    fn write_ranked(path: &str, words: impl IntoIterator<Item = ScoredWord>) -> Result<(), String> {
+        let out_file = match File::create(path) {
+            Ok(f) => f,
+            Err(e) => return Err(format!("Error creating file `{path}`: {e}")),
+        };
+
+        let mut writer = BufWriter::new(out_file);
+        for item in words {
+            if let Err(e) = writeln!(writer, "{}\t{}", item.data, item.count) {
+                return Err(format!("Error writing to file `{path}`: {e}"));
+            }
+        }
+        if let Err(e) = writer.flush() {
+            return Err(format!("Error writing to file `{path}`: {e}"));
+        }
+        Ok(())
+    }
+
+    /// Folds the log kept since `open_with_log` into the dictionary file, by
+    /// appending the logged words to it, and then empties the log. Loading
+    /// the dictionary afterwards gives the same words and ranks as loading it
+    /// and replaying the log did before. The words already added stay as
+    /// they are, and later additions keep being logged.
+    ///
+    /// The dictionary is synced to disk before the log is emptied, so a crash
+    /// in between can't lose words, though it can leave them in both files
+    /// and so counted twice on the next load.
+    ///
+    /// # Return value
+    ///
+    /// Either nothing, or an error string if no log is kept, an earlier append
+    /// to the log failed, or either file could not be written.
+    pub fn compact_log(&mut self) -> Result<(), String> {
+        let log = &self.log;
+        let Some(file) = &log.file else {
+            return Err("No log is kept without `open_with_log`".to_string());
+        };
+        if let Some(e) = &log.error {
+            return Err(e.clone());
+        }
+        let (dict_path, log_path) = (&log.dict_path, &log.log_path);
+
+        let logged = match fs_err::read(log_path) {
+            Ok(bytes) => bytes,
+            Err(e) => return Err(format!("Error reading log `{log_path}`: {e}")),
+        };
+        if logged.is_empty() {
+            return Ok(());
+        }
+        let mut dict = match fs_err::OpenOptions::new().append(true).open(dict_path) {
+            Ok(f) => f,
+            Err(e) => return Err(format!("Error opening file `{dict_path}`: {e}")),
+        };
+        // Start on a fresh line, in case the dictionary doesn't end with one
+        let appended = dict
+            .write_all(b"\n")
+            .and_then(|()| dict.write_all(&logged))
+            .and_then(|()| dict.sync_all());
+        if let Err(e) = appended {
+            return Err(format!("Error writing to file `{dict_path}`: {e}"));
+        }
+
+        if let Err(e) = file.set_len(0).and_then(|()| file.sync_all()) {
+            return Err(format!("Error emptying log `{log_path}`: {e}"));
+        }
+        Ok(())
+    }
+
+    /// Writes every stored word to a CSV file as `word,count` rows, most
+    /// popular first, after a `word,count` header row.
+    ///
+    /// Rows are ordered like `export_sorted`, and can be read back in with
+    /// `from_csv`.
+    ///
+    /// # Arguments
+    ///
+    /// `path` (`&str`) - Name of the file to write. It is created or truncated.
+    ///
+    /// # Return value
+    ///
+    /// Either nothing, or an error string if the file could not be written.
+    #[cfg(feature = "csv")]
+    pub fn to_csv(&self, path: &str) -> Result<(), String> {
+        let out_file = match File::create(path) {
+            Ok(f) => f,
+            Err(e) => return Err(format!("Error creating file `{path}`: {e}")),
+        };
+
+        let mut writer = csv::Writer::from_writer(out_file);
+        if let Err(e) = writer.write_record(["word", "count"]) {
+            return Err(format!("Error writing to file `{path}`: {e}"));
+        }
+        for item in self.words_by_popularity() {
+            if let Err(e) = writer.write_record([item.data, item.count.to_string()]) {
+                return Err(format!("Error writing to file `{path}`: {e}"));
+            }
+        }
+        if let Err(e) = writer.flush() {
+            return Err(format!("Error writing to file `{path}`: {e}"));
+        }
+        Ok(())
+    }
+
+    /// Finds the most frequently added words in the whole dictionary,
+    /// regardless of prefix.
+    ///
+    /// Ties are broken alphabetically, so the result is deterministic.
+    ///
+    /// Only the best `n` words are kept while searching, in a heap, and
+    /// branches that can't beat the worst of them are skipped, so this costs
+    /// O(words log n) at worst rather than sorting the whole dictionary.
+    ///
+    /// # Arguments
+    ///
+    /// `n` (`usize`) - Maximum number of words to return.
+    ///
+    /// # Return value
+    ///
+    /// Up to `n` `(word, rank)` pairs, most frequent first.
+    pub fn most_popular(&self, n: usize) -> Vec<(String, i32)> {
+        if n == 0 {
+            return Vec::new();
+        }
+        let mut best: BinaryHeap<ScoredWord> = BinaryHeap::with_capacity(n.min(self.len()) + 1);
+        let mut unlimited = usize::MAX;
+        Autocompleter::pruned_search(
+            self.trie.get_root(),
+            &mut String::new(),
+            true,
+            n,
+            &mut unlimited,
+            &mut best,
+        );
+        // `ScoredWord` orders best first, so ascending order is ranked order
+        best.into_sorted_vec()
+            .into_iter()
+            .map(|r| (self.surface(r.data), r.count))
+            .collect()
+    }
+
+    /// Finds the word at a given position of the `most_popular` ordering,
+    /// e.g. for showing one entry of a leaderboard. Ties are broken
+    /// alphabetically, as in `most_popular`, so positions are stable.
+    ///
+    /// Only the best `n + 1` words are kept while searching, so this is
+    /// cheapest for small `n`.
+    ///
+    /// # Arguments
+    ///
+    /// `n` (`usize`) - Position to look up, starting from 0 for the most frequent word.
+    ///
+    /// # Return value
+    ///
+    /// The `(word, rank)` pair at position `n`, or `None` if fewer than
+    /// `n + 1` words are stored.
+    pub fn nth_most_popular(&self, n: usize) -> Option<(String, i32)> {
+        if n >= self.len() {
+            return None;
+        }
+        let mut best: BinaryHeap<ScoredWord> = BinaryHeap::with_capacity(n + 2);
+        let mut unlimited = usize::MAX;
+        Autocompleter::pruned_search(
+            self.trie.get_root(),
+            &mut String::new(),
+            true,
+            n + 1,
+            &mut unlimited,
+            &mut best,
+        );
+        // The worst of the best `n + 1` words is on top of the heap
+        best.pop().map(|r| (self.surface(r.data), r.count))
+    }
+
+    /// Keeps only the `k` most frequent words, as picked by `most_popular`,
+    /// removing every other word. Useful for periodically compacting a
+    /// dictionary that has built up a long tail of rare words.
+    ///
+    /// # Arguments
+    ///
+    /// `k` (`usize`) - Number of words to keep.
+    ///
+    /// # Return value
+    ///
+    /// Number of words removed.
+    pub fn trim_to_top_k(&mut self, k: usize) -> usize {
+        if k >= self.len() {
+            return 0;
+        }
+        let keep: HashSet<String> = self
+            .most_popular(k)
+            .into_iter()
+            .map(|(word, _)| word)
+            .collect();
+        self.retain(|word, _| keep.contains(word))
+    }
+
+    /// Collects every stored word, ordered by frequency descending and then
+    /// alphabetically. Used when exporting, where the order shouldn't depend
+    /// on the configured `SortOrder`.
+    fn words_by_popularity(&self) -> Vec<ScoredWord> {
+        let mut all_words = self.depth_first_search(Some(self.trie.get_root()), "");
+        all_words.sort_unstable();
+        all_words
+    }
+
+    /// Picks a random stored word, with each word's chance of being chosen
+    /// proportional to its rank.
+    ///
+    /// Uses weighted reservoir sampling, so the MWT is walked only once and no
+    /// intermediate list of words is built.
+    ///
+    /// # Arguments
+    ///
+    /// `rng` (`&mut impl Rng`) - Source of randomness.
+    ///
+    /// # Return value
+    ///
+    /// The chosen word, or `None` if the `Autocompleter` is empty.
+    #[cfg(feature = "rand")]
+    pub fn sample_word(&self, rng: &mut impl Rng) -> Option<String> {
+        // Every word is offered to the reservoir in turn, replacing the
+        // current choice with probability `rank / total`, where `total`
+        // includes the new word's rank
+        let mut total: u64 = 0;
+        let mut chosen: Option<String> = None;
+        Mwt::for_each_word(self.trie.get_root(), "", false, |word, nd| {
+            if nd.get_rank() > 0 {
+                let weight = nd.get_rank() as u64;
+                total += weight;
+                if rng.random_range(0..total) < weight {
+                    chosen = Some(word.to_string());
+                }
+            }
+        });
+        chosen
+    }
+
+    /// Finds the longest string that every stored word starts with.
+    ///
+    /// Walks down from the root for as long as there is exactly one way to
+    /// continue and no word ends along the way.
+    ///
+    /// # Return value
+    ///
+    /// The shared prefix, which is empty if the words diverge immediately or
+    /// nothing is stored.
+    pub fn longest_common_prefix(&self) -> String {
+        let mut prefix = String::new();
+        let mut tmp = self.trie.get_root();
+
+        while !tmp.get_end() && tmp.child_count() == 1 {
+            match tmp.children_iter().next() {
+                Some((ch, nd)) => {
+                    prefix.push(ch);
+                    tmp = nd;
+                }
+                _ => break,
+            }
+        }
+        prefix
+    }
+
+    /// Computes summary statistics about the stored dictionary.
+    ///
+    /// This requires a walk over every node of the MWT, so it is linear in the
+    /// size of the structure.
+    ///
+    /// # Return value
+    ///
+    /// A `Stats` struct holding the word, occurrence and node counts along with
+    /// the length of the longest word.
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats::default();
+        Autocompleter::collect_stats(self.trie.get_root(), &mut stats);
+        stats.max_word_len = self.max_word_len();
+        stats
+    }
+
+    /// Helper for `stats`. Tallies the given node and every node beneath it
+    /// into `stats`.
+    ///
+    /// # Arguments
+    ///
+    /// `node` (`&MwtNode`) - Node of the MWT to start tallying from
+    ///
+    /// `stats` (`&mut Stats`) - Running totals to update
+    fn collect_stats(node: &MwtNode<T>, stats: &mut Stats) {
+        Mwt::walk(node, "", false, |_, _, nd| {
+            stats.nodes += 1;
+            if nd.get_end() {
+                stats.words += 1;
+                stats.occurrences += nd.get_rank() as u64;
+            }
+            true
+        });
+    }
+
+    /// Tallies how many distinct words share each rank, e.g. that 1500 words
+    /// appear exactly once and 300 appear twice.
+    ///
+    /// Useful for spotting a long tail and choosing a cutoff for `retain` or
+    /// `predict_completions_min_freq`. Linear in the size of the MWT.
+    ///
+    /// # Return value
+    ///
+    /// A map from rank to the number of words with that rank, in increasing
+    /// order of rank.
+    pub fn frequency_histogram(&self) -> BTreeMap<i32, usize> {
+        let mut histogram = BTreeMap::new();
+        Autocompleter::collect_ranks(self.trie.get_root(), &mut histogram);
+        histogram
+    }
+
+    /// Looks up how many distinct lines of the loaded files a word appeared
+    /// on, as opposed to its rank, which counts every appearance. Dividing
+    /// the two, or comparing against the number of lines, gives TF-IDF style
+    /// measures of how distinctive a word is.
+    ///
+    /// Only counted when the `Autocompleter` was built with `track_doc_freq`,
+    /// and only by the loaders that split lines into words, such as
+    /// `add_from_file` and `merge_from_file`. Words added one at a time don't
+    /// count towards it.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`&str`) - Complete word to look up.
+    ///
+    /// # Return value
+    ///
+    /// The word's document frequency, or `None` if `word` isn't stored.
+    pub fn doc_freq_of(&self, word: &str) -> Option<u32> {
+        self.trie
+            .find_node(&self.fold(word))
+            .filter(|nd| nd.get_end())
+            .map(|nd| nd.get_doc_freq())
+    }
+
+    /// Looks up the payload attached to a word with `add_word_with_payload`.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`&str`) - Complete word to look up.
+    ///
+    /// # Return value
+    ///
+    /// The word's payload, or `None` if `word` isn't stored or has none.
+    pub fn payload_of(&self, word: &str) -> Option<&T> {
+        self.trie
+            .find_node(&self.fold(word))
+            .filter(|nd| nd.get_end())
+            .and_then(|nd| nd.get_payload())
+    }
+
+    /// Works out how a word's popularity compares to the rest of the
+    /// dictionary, e.g. 0.8 for "more popular than 80% of words".
+    ///
+    /// Only words with a strictly lower rank count in the word's favour, so
+    /// words tied with it, and the word itself, don't. When every word has the
+    /// same rank, each one's percentile is 0. Linear in the size of the MWT.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`&str`) - Complete word to look up.
+    ///
+    /// # Return value
+    ///
+    /// The fraction of distinct stored words with a lower rank than `word`,
+    /// from 0 up to but not including 1, or `None` if `word` isn't stored.
+    pub fn rank_percentile(&self, word: &str) -> Option<f64> {
+        let rank = self
+            .trie
+            .find_node(&self.fold(word))
+            .filter(|nd| nd.get_end())?
+            .get_rank();
+        let lower: usize = self
+            .frequency_histogram()
+            .range(..rank)
+            .map(|(_, words)| words)
+            .sum();
+        Some(lower as f64 / self.len() as f64)
+    }
+
+    /// Helper for `frequency_histogram`. Counts the rank of every word at or
+    /// below the given node.
+    ///
+    /// # Arguments
+    ///
+    /// `node` (`&MwtNode`) - Node of the MWT to start counting from
+    ///
+    /// `histogram` (`&mut BTreeMap<i32, usize>`) - Running counts to update
+    fn collect_ranks(node: &MwtNode<T>, histogram: &mut BTreeMap<i32, usize>) {
+        Mwt::for_each_word(node, "", false, |_, nd| {
+            *histogram.entry(nd.get_rank()).or_insert(0) += 1;
+        });
+    }
+
+    /// Builds a new `Autocompleter` holding only the words under `prefix`,
+    /// for drilling down into one part of a dictionary.
+    ///
+    /// Words keep their full spelling, prefix included, along with their
+    /// ranks. The new `Autocompleter` has the same settings as this one.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Prefix whose words to copy.
+    ///
+    /// # Return value
+    ///
+    /// The new `Autocompleter`, or `None` if no stored word begins with `prefix`.
+    pub fn subtree(&self, prefix: &str) -> Option<Autocompleter<T>>
+    where
+        T: Clone,
+    {
+        Some(Autocompleter {
+            trie: self.trie.subtree(&self.fold(prefix))?,
+            settings: self.settings.clone(),
+            loaded_files: HashSet::new(),
+            log: WordLog::default(),
+        })
+    }
+
+    /// Lists the characters that can follow `prefix`, with how many stored
+    /// words continue through each one, for "branch explorer" style UIs.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Prefix to look past.
+    ///
+    /// # Return value
+    ///
+    /// `(next_char, word_count)` pairs, most words first and then in character
+    /// order. Empty if no stored word begins with `prefix`.
+    pub fn next_char_breakdown(&self, prefix: &str) -> Vec<(char, usize)> {
+        let nd = match self.trie.find_node(&self.fold(prefix)) {
+            Some(nd) => nd,
+            None => return Vec::new(),
+        };
+        let mut breakdown: Vec<(char, usize)> = nd
+            .children_iter()
+            .map(|(ch, child)| (ch, child.get_subtree_word_count()))
+            .collect();
+        breakdown.sort_unstable_by_key(|&(ch, count)| (Reverse(count), ch));
+        breakdown
+    }
+
+    /// Renders the underlying MWT as an indented text tree for debugging,
+    /// marking nodes that hold a word with `*` and their rank.
+    ///
+    /// The output has a line for every node, so for anything but a small
+    /// dictionary it's best used on a `subtree`.
+    ///
+    /// # Return value
+    ///
+    /// The rendered tree.
+    pub fn dump_tree(&self) -> String {
+        self.trie.dump_tree()
+    }
+
+    /// Renders the underlying MWT as a GraphViz DOT graph, with edges labeled
+    /// by character and word nodes drawn as double circles showing their
+    /// rank. Pipe the output to `dot -Tpng` to get a picture. Like
+    /// `dump_tree`, it's best kept to small dictionaries or a `subtree`.
+    ///
+    /// # Return value
+    ///
+    /// The DOT source.
+    pub fn to_dot(&self) -> String {
+        self.trie.to_dot()
+    }
+
+    /// Gives read-only access to the root of the underlying MWT, for callers
+    /// that want to write their own traversals with `MwtNode::children_iter`.
+    ///
+    /// # Return value
+    ///
+    /// Reference to the root node. It never holds a word itself.
+    pub fn get_root(&self) -> &MwtNode<T> {
+        self.trie.get_root()
+    }
+
+    /// Gives mutable access to the root of the underlying MWT, for callers
+    /// that want to write their own bulk passes over the stored ranks, e.g.
+    /// halving every rank to age a dictionary.
+    ///
+    /// Only ranks can be changed, through `MwtNode::set_rank`, so the set of
+    /// stored words and the counts cached for it stay correct. The bounds
+    /// used to prune predictions are refreshed once the returned guard is
+    /// dropped, which visits every node, so do all the changes for a pass
+    /// through one guard.
+    ///
+    /// # Return value
+    ///
+    /// A guard that dereferences to the root node.
+    pub fn get_root_mut(&mut self) -> RootMut<'_, T> {
+        RootMut::new(&mut self.trie)
+    }
+
+    /// Checks that the underlying MWT is internally consistent: every word is
+    /// stored at the end of its own path with a positive rank, no branch is
+    /// left without a word, and the counts and bounds cached on each node
+    /// match what is actually below it. Meant for tests and for checking
+    /// dictionaries loaded from untrusted data; the walk visits every node.
+    ///
+    /// # Return value
+    ///
+    /// `Ok(())` if the MWT is consistent, or an error naming the first broken
+    /// invariant.
+    pub fn validate(&self) -> Result<(), String> {
+        self.trie.validate()
+    }
+
+    /// Counts the nodes in the underlying MWT, including the root.
+    ///
+    /// # Return value
+    ///
+    /// Total number of nodes. For example after adding "car" and "cat" this is
+    /// 5: the root, "c", "ca", "car" and "cat".
+    pub fn node_count(&self) -> usize {
+        self.trie.node_count()
+    }
+
+    /// Gives a rough estimate, in bytes, of the memory used by the stored
+    /// dictionary.
+    ///
+    /// This counts the size of every node, its children map and its stored
+    /// word, but not allocator overhead, so the real figure will be somewhat
+    /// higher. It's mainly useful for comparing dictionaries with each other.
+    ///
+    /// # Return value
+    ///
+    /// Estimated number of bytes.
+    pub fn estimated_memory_bytes(&self) -> usize {
+        self.trie.estimated_memory_bytes()
+    }
+
+    /// Finds the length of the longest word stored in the `Autocompleter`.
+    ///
+    /// This is the maximum depth of any end node in the MWT, so an empty
+    /// `Autocompleter` reports 0.
+    ///
+    /// # Return value
+    ///
+    /// Length, in characters, of the longest stored word.
+    pub fn max_word_len(&self) -> usize {
+        Autocompleter::max_end_depth(self.trie.get_root(), 0)
+    }
+
+    /// Helper for `max_word_len`. Returns the depth of the deepest end node
+    /// at or below `node`, or 0 if there is none.
+    ///
+    /// # Arguments
+    ///
+    /// `node` (`&MwtNode`) - Node of the MWT to start searching from
+    ///
+    /// `depth` (`usize`) - Number of characters between the root and `node`
+    fn max_end_depth(node: &MwtNode<T>, depth: usize) -> usize {
+        let mut deepest = 0;
+        Mwt::walk(node, "", false, |_, below, nd| {
+            if nd.get_end() {
+                deepest = deepest.max(depth + below);
+            }
+            true
+        });
+        deepest
+    }
+
+    /// Checks whether a prefix is long enough for completions to be offered.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Prefix to check.
+    ///
+    /// # Return value
+    ///
+    /// `true` if `prefix` has at least the configured minimum number of characters.
+    fn long_enough(&self, prefix: &str) -> bool {
+        let min_len = self.settings.min_prefix_len.unwrap_or(MIN_LEN);
+        prefix.chars().count() >= min_len
+    }
+
+    /// Collects every word beginning with `prefix`, unsorted.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// # Return value
+    ///
+    /// The DFS results under the prefix, or nothing if the prefix is shorter
+    /// than the minimum length or not present. The prefix itself is left out
+    /// when `exclude_exact_match` is set.
+    fn candidates(&self, prefix: &str) -> Vec<ScoredWord> {
+        self.candidates_as(prefix)
+    }
+
+    /// Collects every word beginning with `prefix` like `candidates`, as any
+    /// kind of result.
+    fn candidates_as<'a, R: Found<'a, T>>(&'a self, prefix: &str) -> Vec<R> {
+        if !self.long_enough(prefix) {
+            return Vec::new();
+        }
+        let key = self.fold(prefix);
+        let mut results: Vec<R> = match self.trie.find_node(&key) {
+            Some(nd) => self.candidates_below_as(nd, &key),
+            None => Vec::new(),
+        };
+        if self.grapheme_mode() {
+            results.retain(|r| Autocompleter::on_grapheme_boundary(r.word(), key.len()));
+        }
+        results
+    }
+
+    /// Checks whether the `graphemes` option is on. Always `false` without
+    /// the `unicode-segmentation` feature.
+    fn grapheme_mode(&self) -> bool {
+        #[cfg(feature = "unicode-segmentation")]
+        return self.settings.graphemes;
+        #[cfg(not(feature = "unicode-segmentation"))]
+        return false;
+    }
+
+    /// Collects every word at or below the node a prefix leads to, unsorted.
+    ///
+    /// # Arguments
+    ///
+    /// `nd` (`&MwtNode`) - Node at the end of the prefix.
+    ///
+    /// `prefix` (`&str`) - Characters leading from the root to `nd`.
+    ///
+    /// # Return value
+    ///
+    /// The DFS results under the node. The node's own word is left out when
+    /// `exclude_exact_match` is set. With `max_dfs_nodes` set, only the words
+    /// found before the cap was reached are returned.
+    fn candidates_below(&self, nd: &MwtNode<T>, prefix: &str) -> Vec<ScoredWord> {
+        self.candidates_below_as(nd, prefix)
+    }
+
+    /// Collects every word at or below a node like `candidates_below`, as any
+    /// kind of result.
+    fn candidates_below_as<'a, R: Found<'a, T>>(&self, nd: &'a MwtNode<T>, prefix: &str) -> Vec<R> {
+        let mut results = Vec::new();
+        if let Some(mut budget) = self.settings.max_dfs_nodes {
+            Autocompleter::capped_search(
+                nd,
+                &mut prefix.to_string(),
+                !self.settings.exclude_exact_match,
+                &mut budget,
+                &mut results,
+            );
+            return results;
+        }
+        let sorted = cfg!(feature = "deterministic") || self.settings.sorted_traversal;
+        Mwt::for_each_word(nd, prefix, sorted, |word, node| {
+            // Skip the prefix's own word if it's excluded
+            if !(self.settings.exclude_exact_match && std::ptr::eq(node, nd)) {
+                results.push(R::found(node, word));
+            }
+        });
+        results
+    }
+
+    /// Picks what to hand out for a word found at `node` when borrowing,
+    /// which is the node's own copy if words are stored.
+    ///
+    /// # Arguments
+    ///
+    /// `node` (`&MwtNode`) - Node holding the word.
+    ///
+    /// `path` (`&str`) - Characters leading from the root to `node`.
+    ///
+    /// # Return value
+    ///
+    /// The word, borrowed from `node` or copied from `path`.
+    fn word_of<'a>(node: &'a MwtNode<T>, path: &str) -> Cow<'a, str> {
+        match node.stored_word() {
+            "" => Cow::Owned(path.to_string()),
+            data => Cow::Borrowed(data),
+        }
+    }
+
+    /// Finds the best `limit` words beginning with `prefix`, in ranked order.
+    ///
+    /// With the default frequency ranking this runs `pruned_search`, which
+    /// skips any branch whose best rank can't make the cut, so a short prefix
+    /// over a large dictionary doesn't have to visit every word below it. Other
+    /// sort orders, tie breaks and recency ranking need every candidate, so
+    /// they fall back to a full search followed by `sort_results`. When the
+    /// prefix is a complete word with nothing below it, as when a whole word
+    /// has been typed, that word is returned without any search.
+    ///
+    /// With `boost_exact_match`, a prefix that is a stored word is moved to
+    /// the front, and only `limit - 1` other words are kept.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// `limit` (`usize`) - Maximum number of words to return.
+    ///
+    /// # Return value
+    ///
+    /// Up to `limit` `ScoredWord`s, best first.
+    fn top_candidates(&self, prefix: &str, limit: usize) -> Vec<ScoredWord> {
+        let results = if self.merges_casings() {
+            self.merged_candidates(prefix, limit)
+        } else {
+            self.top_candidates_as(prefix, limit)
+        };
+        self.mark_used(&results);
+        results
+    }
+
+    /// Counts every word in a list of results as used, for
+    /// `EvictionPolicy::LeastRecentlyUsed`.
+    ///
+    /// # Arguments
+    ///
+    /// `results` (`&[R]`) - Results about to be returned by a prediction.
+    fn mark_used<R: Ranked>(&self, results: &[R]) {
+        if self.settings.eviction == EvictionPolicy::LeastRecentlyUsed {
+            for r in results {
+                self.trie.mark_used(r.word());
+            }
+        }
+    }
+
+    /// Finds the best `limit` words beginning with any casing of `prefix`,
+    /// with words that only differ in case merged into one, for
+    /// `dedup_folded` when words are stored with their case.
+    ///
+    /// Each merged word has the summed rank and score of its casings, and
+    /// the spelling of the casing with the highest rank, alphabetically first
+    /// among equals. The merged words are then ranked like any others.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// `limit` (`usize`) - Maximum number of words to return.
+    ///
+    /// # Return value
+    ///
+    /// Up to `limit` merged `ScoredWord`s, best first.
+    fn merged_candidates(&self, prefix: &str, limit: usize) -> Vec<ScoredWord> {
+        if limit == 0 || !self.long_enough(prefix) {
+            return Vec::new();
+        }
+        let lower = self.fold(prefix).to_lowercase();
+
+        // Find every node whose path lowercases to the prefix. Each entry
+        // holds the path and how many bytes of `lower` it has matched.
+        let mut matches: Vec<(&MwtNode<T>, String)> = Vec::new();
+        let mut stack = vec![(self.trie.get_root(), String::new(), 0)];
+        while let Some((nd, path, matched)) = stack.pop() {
+            let rest = &lower[matched..];
+            if rest.is_empty() {
+                matches.push((nd, path));
+                continue;
+            }
+            for (ch, child) in nd.children_iter() {
+                let folded: String = ch.to_lowercase().collect();
+                if rest.starts_with(&folded) {
+                    let mut path = path.clone();
+                    path.push(ch);
+                    stack.push((child, path, matched + folded.len()));
+                }
+            }
+        }
+
+        // Merge casings under their lowercased form, remembering the rank of
+        // the casing whose spelling is kept
+        let mut merged: HashMap<String, (ScoredWord, i32)> = HashMap::new();
+        for (nd, path) in matches {
+            let mut found = self.candidates_below(nd, &path);
+            if self.grapheme_mode() {
+                found.retain(|r| Autocompleter::on_grapheme_boundary(&r.data, path.len()));
+            }
+            for r in found {
+                let (kept, kept_rank) = match merged.entry(r.data.to_lowercase()) {
+                    Entry::Vacant(entry) => {
+                        let rank = r.count;
+                        entry.insert((r, rank));
+                        continue;
+                    }
+                    Entry::Occupied(entry) => entry.into_mut(),
+                };
+                let (count, score) = (kept.count.saturating_add(r.count), kept.score + r.score);
+                if (r.count, Reverse(&r.data)) > (*kept_rank, Reverse(&kept.data)) {
+                    *kept_rank = r.count;
+                    *kept = r;
+                }
+                kept.count = count;
+                kept.score = score;
+            }
+        }
+
+        let mut results: Vec<ScoredWord> = merged.into_values().map(|(r, _)| r).collect();
+        self.sort_results(&mut results);
+        if self.settings.boost_exact_match && !self.settings.exclude_exact_match {
+            if let Some(i) = results.iter().position(|r| r.data.to_lowercase() == lower) {
+                let exact = results.remove(i);
+                results.insert(0, exact);
+            }
+        }
+        results.truncate(limit);
+        results
+    }
+
+    /// Finds the best `limit` words beginning with `prefix` like
+    /// `top_candidates`, as any kind of result, e.g. borrowed ones for
+    /// `predict_completions_ref`.
+    fn top_candidates_as<'a, R: Found<'a, T>>(&'a self, prefix: &str, limit: usize) -> Vec<R> {
+        if limit == 0 || !self.long_enough(prefix) {
+            return Vec::new();
+        }
+        let key = self.fold(prefix);
+        let nd = match self.trie.find_node(&key) {
+            Some(nd) => nd,
+            None => return Vec::new(),
+        };
+        // A leaf's only completion is its own word, whatever the ranking.
+        // Sorting one word is free, but brings its recency score up to date.
+        if nd.child_count() == 0 {
+            if !nd.get_end() || self.settings.exclude_exact_match {
+                return Vec::new();
+            }
+            let mut results = vec![R::found(nd, &key)];
+            self.sort_results(&mut results);
+            return results;
+        }
+
+        let boost =
+            self.settings.boost_exact_match && !self.settings.exclude_exact_match && nd.get_end();
+
+        if !self.plain_ranking() || self.grapheme_mode() {
+            let mut results: Vec<R> = self.candidates_as(prefix);
+            self.sort_results(&mut results);
+            if boost {
+                if let Some(i) = results.iter().position(|r| r.word() == key) {
+                    let exact = results.remove(i);
+                    results.insert(0, exact);
+                }
+            }
+            results.truncate(limit);
+            return results;
+        }
+
+        let others = limit - usize::from(boost);
+        let mut best = BinaryHeap::with_capacity(others + 1);
+        if others > 0 {
+            Autocompleter::pruned_search(
+                nd,
+                &mut key.to_string(),
+                !self.settings.exclude_exact_match && !boost,
+                others,
+                &mut self.settings.max_dfs_nodes.unwrap_or(usize::MAX),
+                &mut best,
+            );
+        }
+        // Results order best first, so ascending order is ranked order
+        let mut results = best.into_sorted_vec();
+        if boost {
+            results.insert(0, R::found(nd, &key));
+        }
+        results
+    }
+
+    /// Checks whether predictions are ranked by frequency with alphabetical
+    /// tie breaks and nothing else, which is the order `ScoredWord` itself
+    /// sorts in. Searches can then rank with a heap instead of a full sort.
+    fn plain_ranking(&self) -> bool {
+        self.settings.sort_order == SortOrder::FrequencyThenAlpha
+            && self.settings.tie_break == TieBreak::Alphabetical
+            && self.settings.recency_decay.is_none()
+            && !self.settings.float_scores
+            && self.settings.ranker.is_none()
+    }
+
+    /// Variant of `depth_first_search` that only keeps the best `limit`
+    /// words, used by `top_candidates`.
+    ///
+    /// Children are visited in order of their `max_subtree_rank`, highest
+    /// first. Once `limit` words have been found, a child whose bound is below
+    /// the worst of them can't contribute, and neither can any child after it.
+    ///
+    /// # Arguments
+    ///
+    /// `node` (`&MwtNode`) - Node of the MWT to start searching from
+    ///
+    /// `path` (`&mut String`) - Characters leading from the root to `node`
+    ///
+    /// `include_self` (`bool`) - Whether `node`'s own word can be collected
+    ///
+    /// `limit` (`usize`) - Number of words to keep
+    ///
+    /// `budget` (`&mut usize`) - Number of nodes still allowed to be visited, for
+    /// `max_dfs_nodes`. The search stops wherever it is once this reaches 0.
+    ///
+    /// `best` (`&mut BinaryHeap<R>`) - The best words found so far, with the worst of them
+    /// on top
+    fn pruned_search<'a, R: Found<'a, T>>(
+        node: &'a MwtNode<T>,
+        path: &mut String,
+        include_self: bool,
+        limit: usize,
+        budget: &mut usize,
+        best: &mut BinaryHeap<R>,
+    ) {
+        let start = path.len();
+        // Each entry holds the length of its parent's path, and children are
+        // pushed lowest bound first so the highest is searched first
+        let mut stack: Vec<(usize, Option<char>, &'a MwtNode<T>)> = vec![(start, None, node)];
+        while let Some((len, ch, nd)) = stack.pop() {
+            // A tie on rank can still win on alphabetical order, so only
+            // strictly lower bounds are cut off. Bounds only get cut off as
+            // the worst kept word improves, so skipping here is as good as
+            // never having pushed the node.
+            if best.len() == limit
+                && best
+                    .peek()
+                    .is_some_and(|worst| nd.get_max_subtree_rank() < worst.count())
+            {
+                continue;
+            }
+            if *budget == 0 {
+                break;
+            }
+            *budget -= 1;
+            path.truncate(len);
+            path.extend(ch);
+            if (include_self || ch.is_some()) && nd.get_end() {
+                best.push(R::found(nd, path));
+                if best.len() > limit {
+                    best.pop();
+                }
+            }
+
+            // Sorting the children in place on the stack saves collecting them first
+            let len = path.len();
+            let first = stack.len();
+            stack.extend(nd.children_iter().map(|(ch, child)| (len, Some(ch), child)));
+            stack[first..].sort_unstable_by_key(|&(_, _, child)| child.get_max_subtree_rank());
+        }
+        path.truncate(start);
+    }
+
+    /// Variant of `depth_first_search` that stops once it has visited a set
+    /// number of nodes, used by `candidates_below` for `max_dfs_nodes`.
+    ///
+    /// Children are visited in order of their `max_subtree_rank`, highest
+    /// first, so when the cap cuts the search short the words already found
+    /// lean towards the most popular ones.
+    ///
+    /// # Arguments
+    ///
+    /// `node` (`&MwtNode`) - Node of the MWT to start searching from
+    ///
+    /// `path` (`&mut String`) - Characters leading from the root to `node`
+    ///
+    /// `include_self` (`bool`) - Whether `node`'s own word can be collected
+    ///
+    /// `budget` (`&mut usize`) - Number of nodes still allowed to be visited
+    ///
+    /// `ret` (`&mut Vec<R>`) - Vector the found words are pushed onto
+    fn capped_search<'a, R: Found<'a, T>>(
+        node: &'a MwtNode<T>,
+        path: &mut String,
+        include_self: bool,
+        budget: &mut usize,
+        ret: &mut Vec<R>,
+    ) {
+        let start = path.len();
+        // Each entry holds the length of its parent's path, and children are
+        // pushed last in visiting order so the first pops off first
+        let mut stack: Vec<(usize, Option<char>, &'a MwtNode<T>)> = vec![(start, None, node)];
+        while let Some((len, ch, nd)) = stack.pop() {
+            if *budget == 0 {
+                break;
+            }
+            *budget -= 1;
+            path.truncate(len);
+            path.extend(ch);
+            if (include_self || ch.is_some()) && nd.get_end() {
+                ret.push(R::found(nd, path));
+            }
+
+            let len = path.len();
+            let first = stack.len();
+            stack.extend(nd.children_iter().map(|(ch, child)| (len, Some(ch), child)));
+            stack[first..].sort_unstable_by_key(|&(_, ch, child)| {
+                (child.get_max_subtree_rank(), Reverse(ch))
+            });
+        }
+        path.truncate(start);
+    }
+
+    /// Sorts a set of DFS results according to the configured `SortOrder`.
+    ///
+    /// When recency ranking is enabled, the decayed score takes the place of
+    /// the raw frequency in every comparison. Frequency ties under
+    /// `SortOrder::FrequencyThenAlpha` are broken according to the `TieBreak`.
+    /// A ranker set with `set_ranker` overrides all of this.
+    ///
+    /// # Arguments
+    ///
+    /// `results` (`&mut [R]`) - Results to sort in place.
+    fn sort_results<R: Ranked>(&self, results: &mut [R]) {
+        if let Some(Ranker(ranker)) = &self.settings.ranker {
+            // Highest score first, with alphabetical order breaking ties
+            results.sort_by(|a, b| {
+                let by_score = ranker(b.word(), b.count()).total_cmp(&ranker(a.word(), a.count()));
+                by_score.then_with(|| a.word().cmp(b.word()))
+            });
+            return;
+        }
+
+        let decay = self.settings.recency_decay;
+        if let Some(decay) = decay {
+            // Bring every score up to the current clock so they can be compared directly.
+            let now = self.trie.get_clock();
+            for res in results.iter_mut() {
+                res.set_recency(res.recency() * decay.powf((now - res.last_seen()) as f64));
+            }
+        }
+
+        // Higher frequency (or float or recency score, when enabled) sorts first.
+        let float_scores = self.settings.float_scores;
+        let by_frequency = |a: &R, b: &R| match decay {
+            _ if float_scores => b.score().total_cmp(&a.score()),
+            Some(_) => b.recency().total_cmp(&a.recency()),
+            None => b.count().cmp(&a.count()),
+        };
 
-            for value in children.values() {
-                let recursive_res = Autocompleter::depth_first_search(value.as_ref());
-                if !recursive_res.is_empty() {
-                    for item in recursive_res {
-                        ret.push(item);
+        match self.settings.sort_order {
+            SortOrder::FrequencyThenAlpha => {
+                // Sort by the tie break first, then stable sort on frequency second
+                // Frequency sort should be reversed from largest to smallest
+                match self.settings.tie_break {
+                    TieBreak::Alphabetical => {
+                        results.sort_unstable_by(|a, b| a.word().cmp(b.word()))
                     }
+                    TieBreak::ShorterFirst => results.sort_unstable_by(|a, b| {
+                        let by_len = a.word().chars().count().cmp(&b.word().chars().count());
+                        by_len.then_with(|| a.word().cmp(b.word()))
+                    }),
+                    TieBreak::LongerFirst => results.sort_unstable_by(|a, b| {
+                        let by_len = b.word().chars().count().cmp(&a.word().chars().count());
+                        by_len.then_with(|| a.word().cmp(b.word()))
+                    }),
                 }
+                results.sort_by(by_frequency);
+            }
+            SortOrder::AlphaThenFrequency => {
+                results.sort_by(|a, b| a.word().cmp(b.word()).then_with(|| by_frequency(a, b)));
+            }
+            SortOrder::AlphaOnly => results.sort_unstable_by(|a, b| a.word().cmp(b.word())),
+        }
+    }
+
+    /// Variant of `depth_first_search` that doesn't descend past a maximum
+    /// depth, used by `predict_completions_max_len`.
+    ///
+    /// # Arguments
+    ///
+    /// `node` (`&MwtNode`) - Node of the MWT to start searching from
+    ///
+    /// `path` (`&str`) - Characters leading from the root to `node`
+    ///
+    /// `depth` (`usize`) - Number of characters between the root and `node`
+    ///
+    /// `max_depth` (`usize`) - Deepest level, i.e. longest word, to collect
+    ///
+    /// `ret` (`&mut Vec<ScoredWord>`) - Vector the found words are pushed onto
+    fn depth_limited_search(
+        node: &MwtNode<T>,
+        path: &str,
+        depth: usize,
+        max_depth: usize,
+        ret: &mut Vec<ScoredWord>,
+    ) {
+        Mwt::walk(node, path, false, |word, below, nd| {
+            if nd.get_end() {
+                ret.push(ScoredWord::from_node(nd, word));
+            }
+            depth + below < max_depth
+        });
+    }
+
+    /// Variant of `depth_first_search` that stops descending a branch at the
+    /// first word it finds, used by `predict_shortest_completions`.
+    ///
+    /// # Arguments
+    ///
+    /// `node` (`&MwtNode`) - Node of the MWT to start searching from
+    ///
+    /// `path` (`&str`) - Characters leading from the root to `node`
+    ///
+    /// `ret` (`&mut Vec<ScoredWord>`) - Vector the found words are pushed onto
+    fn shortest_search(node: &MwtNode<T>, path: &str, ret: &mut Vec<ScoredWord>) {
+        Mwt::walk(node, path, false, |word, _, nd| {
+            if nd.get_end() {
+                ret.push(ScoredWord::from_node(nd, word));
+                return false;
+            }
+            true
+        });
+    }
+
+    /// Works out how many bytes at the start of `word` were matched by
+    /// `prefix`. Matching is exact apart from `ignore_punctuation`, where the
+    /// match also spans any punctuation and whitespace the prefix skipped.
+    fn matched_len(&self, prefix: &str, word: &str) -> usize {
+        if !self.settings.ignore_punctuation {
+            return prefix.len().min(word.len());
+        }
+        let mut to_match = prefix.chars().count();
+        for (i, ch) in word.char_indices() {
+            if to_match == 0 {
+                return i;
+            }
+            if !Autocompleter::is_ignored(ch) {
+                to_match -= 1;
             }
         }
+        word.len()
+    }
+
+    /// This function is used in the second half of `predict_completions`.
+    /// Once the correct ending node of the prefix is found, we iteratively
+    /// search the rest of the Trie looking for all completed words and add
+    /// them to the return vector.
+    ///
+    /// Children are visited in hash map order, or in character order when
+    /// `sorted_traversal` is set or the `deterministic` feature is enabled.
+    ///
+    /// # Arguments
+    ///
+    /// `node` (`Option<&MwtNode>`) - Node of the MWT to start searching from
+    ///
+    /// `path` (`&str`) - Characters leading from the root to `node`. Words are built
+    /// from it, so they don't need to be stored in the nodes.
+    ///
+    /// # Return value
+    ///
+    /// A vector of tuples, where the first value is the frequency and the second is the
+    /// word corresponding to that frequency.
+    fn depth_first_search(&self, node: Option<&MwtNode<T>>, path: &str) -> Vec<ScoredWord> {
+        let mut ret: Vec<ScoredWord> = Vec::new();
+        if let Some(nd) = node {
+            let sorted = cfg!(feature = "deterministic") || self.settings.sorted_traversal;
+            Mwt::for_each_word(nd, path, sorted, |word, nd| {
+                ret.push(ScoredWord::from_node(nd, word));
+            });
+        }
         ret
     }
 }
+
+impl PartialEq for ScoredWord {
+    fn eq(&self, other: &ScoredWord) -> bool {
+        self.count == other.count && self.data == other.data
+    }
+}
+
+impl Eq for ScoredWord {}
+
+impl PartialOrd for ScoredWord {
+    fn partial_cmp(&self, other: &ScoredWord) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ScoredWord {
+    fn cmp(&self, other: &ScoredWord) -> std::cmp::Ordering {
+        other
+            .count
+            .cmp(&self.count)
+            .then_with(|| self.data.cmp(&other.data))
+    }
+}
+
+/// Borrowed results order the same way as `ScoredWord`, so the searches rank
+/// them alike.
+impl<T> PartialEq for BorrowedWord<'_, T> {
+    fn eq(&self, other: &BorrowedWord<'_, T>) -> bool {
+        self.count() == other.count() && self.word == other.word
+    }
+}
+
+impl<T> Eq for BorrowedWord<'_, T> {}
+
+impl<T> PartialOrd for BorrowedWord<'_, T> {
+    fn partial_cmp(&self, other: &BorrowedWord<'_, T>) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for BorrowedWord<'_, T> {
+    fn cmp(&self, other: &BorrowedWord<'_, T>) -> std::cmp::Ordering {
+        other
+            .count()
+            .cmp(&self.count())
+            .then_with(|| self.word.cmp(&other.word))
+    }
+}
+
+impl Default for Autocompleter {
+    fn default() -> Self {
+        Autocompleter::new()
+    }
+}
+
+/// Parses a string of whitespace separated words, as with `from_text`, so
+/// `"the quick brown fox".parse::<Autocompleter>()` works.
+impl FromStr for Autocompleter {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Ok(Autocompleter::from_text(text))
+    }
+}
+
+/// Two `Autocompleter`s are equal when they store the same words with the same
+/// ranks. Insertion order and settings are not compared.
+impl<T> PartialEq for Autocompleter<T> {
+    fn eq(&self, other: &Autocompleter<T>) -> bool {
+        self.trie == other.trie
+    }
+}
+
+/// Consumes the `Autocompleter`, yielding every stored word with its rank as
+/// `(word, rank)` pairs, in no particular order. The MWT is dismantled as it
+/// goes, so words are moved out instead of cloned.
+impl<T> IntoIterator for Autocompleter<T> {
+    type Item = (String, i32);
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        self.trie.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds an `Autocompleter` from `(word, count)` pairs.
+    fn with_counts(builder: AutocompleterBuilder, words: &[(&str, i32)]) -> Autocompleter {
+        let mut ac = builder.build();
+        for &(word, count) in words {
+            ac.add_word_with_count(word.to_string(), count);
+        }
+        ac
+    }
+
+    /// Path of a scratch file in the temp directory, unique to this test run.
+    fn temp_path(name: &str) -> String {
+        let file = format!("rustocompleter-{}-{name}", std::process::id());
+        std::env::temp_dir()
+            .join(file)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Writes `contents` to a scratch file, returning its path.
+    fn temp_file(name: &str, contents: &[u8]) -> String {
+        let path = temp_path(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn sort_orders_rank_the_same_words_differently() {
+        let words = [("bat", 1), ("ball", 3), ("band", 3), ("banana", 2)];
+        let by_order = |order| {
+            with_counts(Autocompleter::builder().sort_order(order), &words)
+                .predict_completions("ba")
+        };
+
+        assert_eq!(
+            by_order(SortOrder::FrequencyThenAlpha),
+            ["ball", "band", "banana", "bat"]
+        );
+        assert_eq!(
+            by_order(SortOrder::AlphaThenFrequency),
+            ["ball", "banana", "band", "bat"]
+        );
+        assert_eq!(
+            by_order(SortOrder::AlphaOnly),
+            ["ball", "banana", "band", "bat"]
+        );
+        assert_eq!(
+            with_counts(Autocompleter::builder(), &words).predict_completions("ba"),
+            by_order(SortOrder::FrequencyThenAlpha)
+        );
+    }
+
+    #[test]
+    fn prefix_length_is_counted_in_characters() {
+        let mut ac = Autocompleter::new();
+        ac.add_word("école".to_string());
+        assert_eq!(ac.predict_completions("é"), ["école"]);
+
+        let mut ac = Autocompleter::builder().min_prefix_len(2).build();
+        ac.add_word("école".to_string());
+        // "é" is two bytes, but only one character
+        assert!(ac.predict_completions("é").is_empty());
+        assert_eq!(ac.predict_completions("éc"), ["école"]);
+    }
+
+    #[test]
+    fn recent_word_overtakes_a_historically_frequent_one() {
+        let mut decayed = Autocompleter::builder().recency_decay(0.9).build();
+        let mut plain = Autocompleter::new();
+        for ac in [&mut decayed, &mut plain] {
+            for _ in 0..100 {
+                ac.add_word("season".to_string());
+            }
+            for _ in 0..10 {
+                ac.add_word("seal".to_string());
+            }
+        }
+
+        assert_eq!(decayed.predict_completions("sea"), ["seal", "season"]);
+        assert_eq!(plain.predict_completions("sea"), ["season", "seal"]);
+    }
+
+    #[test]
+    fn empty_word_is_never_stored() {
+        let mut ac = Autocompleter::new();
+        ac.add_word(String::new());
+        assert_eq!(ac.add_word_counted(String::new()), 0);
+        ac.add_word("a".to_string());
+
+        assert_eq!(ac.len(), 1);
+        assert!(!ac.get_root().get_end());
+        assert!(!ac.predict_completions("a").iter().any(|w| w.is_empty()));
+        assert!(ac.words().all(|w| !w.is_empty()));
+        assert!(ac.into_iter().all(|(w, _)| !w.is_empty()));
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn csv_round_trip_keeps_words_and_ranks() {
+        let ac = with_counts(
+            Autocompleter::builder(),
+            &[("apple", 3), ("hello, world", 2), ("say \"hi\"", 1)],
+        );
+        let path = temp_path("round-trip.csv");
+        ac.to_csv(&path).unwrap();
+        let loaded = Autocompleter::from_csv(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.to_vec(), ac.to_vec());
+        assert_eq!(loaded.rank_of("hello, world"), Some(2));
+    }
+
+    #[cfg(feature = "stemming")]
+    #[test]
+    fn stemming_merges_variants_into_one_prediction() {
+        let mut ac = Autocompleter::builder().stem(true).build();
+        ac.add_words(["run", "running", "runs"].map(String::from));
+
+        assert_eq!(ac.predict_completions("ru"), ["run"]);
+        assert_eq!(ac.rank_of("run"), Some(3));
+    }
+
+    #[test]
+    fn clone_is_independent_of_the_original() {
+        let original = with_counts(Autocompleter::builder(), &[("car", 2), ("cart", 1)]);
+        let mut copy = original.clone();
+        copy.add_word("cat".to_string());
+        copy.add_word("car".to_string());
+
+        assert_eq!(original.predict_completions("ca"), ["car", "cart"]);
+        assert_eq!(original.rank_of("car"), Some(2));
+        assert_eq!(copy.predict_completions("ca"), ["car", "cart", "cat"]);
+        assert_eq!(copy.rank_of("car"), Some(3));
+        copy.validate().unwrap();
+    }
+
+    #[test]
+    fn max_words_rejects_new_words_once_full() {
+        let mut ac = Autocompleter::builder().max_words(2).build();
+        ac.add_words(["alpha", "beta", "gamma"].map(String::from));
+        assert_eq!(ac.add_word_counted("alpha".to_string()), 2);
+        assert_eq!(ac.add_word_counted("delta".to_string()), 0);
+
+        assert_eq!(ac.len(), 2);
+        assert!(!ac.contains("gamma"));
+        assert_eq!(ac.rank_of("alpha"), Some(2));
+        ac.validate().unwrap();
+    }
+
+    #[test]
+    fn least_recently_used_words_are_evicted_first() {
+        let mut ac = Autocompleter::builder()
+            .max_words(3)
+            .eviction_policy(EvictionPolicy::LeastRecentlyUsed)
+            .build();
+        ac.add_words(["one", "two", "three"].map(String::from));
+        // Adding "one" again in any way makes it the newest, so "two" goes first
+        ac.set_rank("one", 5);
+        ac.add_words(["four", "five"].map(String::from));
+
+        let mut words: Vec<String> = ac.words().map(String::from).collect();
+        words.sort();
+        assert_eq!(words, ["five", "four", "one"]);
+        assert_eq!(ac.rank_of("one"), Some(5));
+        ac.validate().unwrap();
+    }
+
+    #[test]
+    fn min_freq_hides_rare_completions() {
+        let ac = with_counts(
+            Autocompleter::builder(),
+            &[("tea", 5), ("team", 3), ("tear", 1), ("teal", 1)],
+        );
+
+        assert_eq!(
+            ac.predict_completions_min_freq("te", 3, 10),
+            ["tea", "team"]
+        );
+        assert_eq!(
+            ac.predict_completions_min_freq("te", 1, 10),
+            ac.predict_completions_n("te", 10)
+        );
+        assert!(ac.predict_completions_min_freq("te", 6, 10).is_empty());
+    }
+
+    #[test]
+    fn node_count_is_exact_on_a_small_dictionary() {
+        let mut ac = Autocompleter::new();
+        ac.add_words(["to", "tea", "ten"].map(String::from));
+
+        // The root, "t", "to", "te", "tea" and "ten"
+        assert_eq!(ac.node_count(), 6);
+        assert_eq!(Autocompleter::estimate_nodes(["to", "tea", "ten"]), 6);
+        assert!(ac.estimated_memory_bytes() >= 6 * std::mem::size_of::<MwtNode>());
+    }
+
+    #[test]
+    fn blank_files_load_as_empty_completers() {
+        let cases: [(&str, &[u8]); 3] = [
+            ("empty.txt", b""),
+            ("whitespace.txt", b"   \n\t\n  \r\n"),
+            ("punctuation.txt", b"... !!! ,\n-- ?\n"),
+        ];
+        for (name, contents) in cases {
+            let path = temp_file(name, contents);
+            let ac = Autocompleter::from_file(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(ac.len(), 0, "{name}");
+            assert!(!ac.get_root().get_end(), "{name}");
+            ac.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn bom_and_crlf_are_stripped_when_loading() {
+        let path = temp_file("bom-crlf.txt", "\u{FEFF}hello world\r\nhelp\r\n".as_bytes());
+        let ac = Autocompleter::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(ac.predict_completions("hel"), ["hello", "help"]);
+        assert!(ac.contains("world"));
+        assert!(ac.words().all(|w| !w.contains(['\u{FEFF}', '\r'])));
+    }
+
+    #[test]
+    fn ranks_saturate_instead_of_overflowing() {
+        let mut ac = with_counts(Autocompleter::builder(), &[("big", i32::MAX - 1)]);
+        ac.add_word("big".to_string());
+        assert_eq!(ac.rank_of("big"), Some(i32::MAX));
+        ac.add_word("big".to_string());
+        ac.add_word_with_count("big".to_string(), i32::MAX);
+        assert_eq!(ac.rank_of("big"), Some(i32::MAX));
+        assert_eq!(ac.predict_completions("b"), ["big"]);
+        ac.validate().unwrap();
+    }
+
+    #[test]
+    fn keep_chars_survive_punctuation_trimming() {
+        let path = temp_file("keep-chars.txt", b"#rust c# f#, done.\n");
+        let mut kept = Autocompleter::builder().keep_chars(['#']).build();
+        kept.add_from_file(&path).unwrap();
+        let default = Autocompleter::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(kept.contains("#rust"));
+        assert_eq!(kept.predict_completions("c"), ["c#"]);
+        assert_eq!(kept.predict_completions("f"), ["f#"]);
+        assert_eq!(kept.predict_completions("d"), ["done"]);
+        assert_eq!(default.predict_completions("c"), ["c"]);
+    }
+
+    #[test]
+    fn predictions_move_words_out_unchanged() {
+        let ac = with_counts(
+            Autocompleter::builder(),
+            &[("hi", 2), ("hill", 3), ("him", 1)],
+        );
+        let node = ac
+            .get_root()
+            .get_child('h')
+            .and_then(|nd| nd.get_child('i'));
+        #[cfg(feature = "store-words")]
+        assert_eq!(node.map(MwtNode::get_data), Some("hi"));
+        assert!(node.is_some_and(MwtNode::get_end));
+
+        let counted: Vec<String> = ac
+            .predict_completions_with_counts("h", ELEMENTS_TO_RETURN)
+            .into_iter()
+            .map(|(word, _)| word)
+            .collect();
+        assert_eq!(ac.predict_completions("h"), ["hill", "hi", "him"]);
+        assert_eq!(ac.predict_completions("h"), counted);
+    }
+
+    #[test]
+    fn prefixes_shorter_than_the_minimum_return_nothing() {
+        let mut ac = Autocompleter::builder().min_prefix_len(3).build();
+        ac.add_words(["cat", "catalog"].map(String::from));
+
+        assert!(ac.predict_completions("ca").is_empty());
+        assert_eq!(
+            ac.predict_completions_result("ca"),
+            Err(PredictError::PrefixTooShort { min_len: 3 })
+        );
+        assert_eq!(ac.predict_completions("cat"), ["cat", "catalog"]);
+    }
+
+    #[test]
+    fn batch_prediction_keeps_input_order() {
+        let ac = with_counts(
+            Autocompleter::builder(),
+            &[("dog", 2), ("door", 1), ("cat", 1)],
+        );
+        let results = ac.predict_batch(["do", "zebra", "c"], 1);
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0], ["dog"]);
+        assert!(results[1].is_empty());
+        assert_eq!(results[2], ["cat"]);
+    }
+
+    #[test]
+    fn case_folding_is_the_same_for_inserts_and_queries() {
+        let mut ac = Autocompleter::builder().case_insensitive(true).build();
+        ac.add_words(["İstanbul", "STRASSE", "Rust"].map(String::from));
+
+        // Whatever the fold does, a word is always found by its own spelling
+        for word in ["İstanbul", "STRASSE", "Rust"] {
+            assert!(ac.contains(word), "{word}");
+            assert_eq!(ac.predict_completions(word).len(), 1, "{word}");
+        }
+        assert_eq!(ac.predict_completions("RU"), ["rust"]);
+        // `to_lowercase` keeps the combining dot and doesn't expand "ß"
+        assert_eq!(ac.predict_completions("İst"), ["i\u{307}stanbul"]);
+        assert!(!ac.contains("istanbul"));
+        assert!(ac.contains("strasse"));
+        assert!(!ac.contains("straße"));
+    }
+
+    #[test]
+    fn over_long_tokens_are_skipped() {
+        let long = "x".repeat(50);
+        let path = temp_file("long-token.txt", format!("short {long} fine\n").as_bytes());
+        let mut ac = Autocompleter::builder().max_word_len(10).build();
+        ac.add_from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        ac.add_word("y".repeat(11));
+        ac.add_word("z".repeat(10));
+
+        assert_eq!(ac.len(), 3);
+        assert!(!ac.contains(&long));
+        assert!(ac.contains("short") && ac.contains("fine"));
+        assert_eq!(ac.max_word_len(), 10);
+        // Skipped words don't leave any nodes behind
+        assert_eq!(
+            ac.node_count(),
+            Autocompleter::estimate_nodes(["short", "fine", "zzzzzzzzzz"])
+        );
+    }
+
+    #[test]
+    fn phrases_keep_their_spaces() {
+        let path = temp_file("phrases.txt", b"machine learning\nmachine vision\n");
+        let mut phrases = Autocompleter::new();
+        phrases.add_phrases_from_file(&path).unwrap();
+        let split = Autocompleter::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        phrases.add_phrase("machine learning");
+        phrases.add_word("machine".to_string());
+
+        assert_eq!(
+            phrases.predict_phrases("mach"),
+            ["machine learning", "machine vision"]
+        );
+        assert_eq!(phrases.rank_of("machine learning"), Some(2));
+        // `from_file` still splits lines into words
+        assert_eq!(split.predict_completions("mach"), ["machine"]);
+        assert!(split.predict_phrases("mach").is_empty());
+    }
+
+    #[test]
+    fn deep_chain_survives_every_search() {
+        let deep = "a".repeat(200_000);
+        let mut ac = Autocompleter::new();
+        ac.add_word(deep.clone());
+        ac.add_word("ab".to_string());
+
+        assert_eq!(ac.predict_completions("a").len(), 2);
+        assert_eq!(ac.words_with_prefix("a").len(), 2);
+        assert_eq!(ac.predict_shortest_completions("a").len(), 2);
+        assert_eq!(ac.predict_completions_max_len("a", 5, 10), ["ab"]);
+        assert_eq!(ac.most_popular(1).len(), 1);
+        assert_eq!(ac.max_word_len(), deep.len());
+        assert_eq!(ac.stats().nodes, deep.len() + 2);
+        assert_eq!(ac.frequency_histogram().get(&1), Some(&2));
+        assert_eq!(ac.suggest_correction("ac").as_deref(), Some("ab"));
+        drop(ac.get_root_mut());
+        ac.validate().unwrap();
+        drop(ac);
+    }
+
+    #[test]
+    fn cached_word_counts_follow_inserts_and_removals() {
+        let mut ac = Autocompleter::new();
+        ac.add_words(["car", "card", "care", "cat", "dog"].map(String::from));
+        ac.add_word("card".to_string());
+        assert_eq!(ac.prefix_count("ca"), 4);
+        assert_eq!(ac.prefix_count("car"), 3);
+        assert_eq!(ac.prefix_count(""), 5);
+        assert_eq!(ac.next_char_breakdown("ca"), [('r', 3), ('t', 1)]);
+
+        ac.remove_word("car");
+        ac.remove_word("card");
+        assert_eq!(ac.prefix_count("car"), 1);
+        assert_eq!(ac.next_char_breakdown("ca"), [('r', 1), ('t', 1)]);
+        ac.remove_word("care");
+        assert_eq!(ac.prefix_count("car"), 0);
+        assert_eq!(ac.next_char_breakdown("ca"), [('t', 1)]);
+        ac.remove_prefix("d");
+        ac.add_word("cart".to_string());
+        assert_eq!(ac.prefix_count(""), 2);
+        assert_eq!(ac.get_root().get_subtree_word_count(), ac.len());
+        ac.validate().unwrap();
+    }
+
+    #[test]
+    fn irregular_spacing_matches_stored_phrases() {
+        let mut ac = Autocompleter::builder().normalize_whitespace(true).build();
+        ac.add_phrase("new york");
+        ac.add_phrase("new   york");
+
+        assert_eq!(ac.rank_of("new\t york"), Some(2));
+        assert_eq!(ac.predict_completions("new  y"), ["new york"]);
+        assert_eq!(ac.predict_phrases("new    "), ["new york"]);
+    }
+
+    #[test]
+    fn tie_breaks_order_equally_frequent_words() {
+        let words = [("pear", 2), ("peach", 2), ("pea", 2), ("pecan", 3)];
+        let by_tie_break = |tie_break| {
+            with_counts(Autocompleter::builder().tie_break(tie_break), &words)
+                .predict_completions("pe")
+        };
+
+        assert_eq!(
+            by_tie_break(TieBreak::Alphabetical),
+            ["pecan", "pea", "peach", "pear"]
+        );
+        assert_eq!(
+            by_tie_break(TieBreak::ShorterFirst),
+            ["pecan", "pea", "pear", "peach"]
+        );
+        assert_eq!(
+            by_tie_break(TieBreak::LongerFirst),
+            ["pecan", "peach", "pear", "pea"]
+        );
+        assert_eq!(
+            with_counts(Autocompleter::builder(), &words).predict_completions("pe"),
+            by_tie_break(TieBreak::Alphabetical)
+        );
+    }
+
+    #[test]
+    fn lenient_loading_skips_invalid_utf8_lines() {
+        let path = temp_file("lenient.txt", b"alpha\nbeta\ngam\xffma\ndelta\n");
+        let mut ac = Autocompleter::new();
+        let mut skips = Vec::new();
+        let summary = ac.add_from_file_lenient(&path, |line, error| {
+            skips.push((line, error.to_string()));
+        });
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(summary, Ok((3, 1)));
+        assert_eq!(skips.len(), 1);
+        assert_eq!(skips[0].0, 3);
+        assert!(
+            skips[0].1.starts_with("error on line 3: "),
+            "{}",
+            skips[0].1
+        );
+        let mut words: Vec<String> = ac.words().map(String::from).collect();
+        words.sort();
+        assert_eq!(words, ["alpha", "beta", "delta"]);
+    }
+
+    #[test]
+    fn compact_keeps_predictions_without_growing() {
+        let mut ac = Autocompleter::new();
+        for i in 0..200 {
+            ac.add_word(format!("word{i}"));
+        }
+        ac.add_word("word7".to_string());
+        ac.retain(|word, _| word.len() < 6);
+        let before = ac.predict_completions("w");
+        let nodes = ac.node_count();
+
+        ac.compact();
+        assert_eq!(ac.predict_completions("w"), before);
+        assert!(ac.node_count() <= nodes);
+        assert_eq!(ac.rank_of("word7"), Some(2));
+        ac.validate().unwrap();
+    }
+
+    #[test]
+    fn ranker_can_prefer_shorter_words() {
+        let mut ac = with_counts(
+            Autocompleter::builder(),
+            &[("format", 9), ("form", 2), ("formula", 5), ("fork", 1)],
+        );
+        ac.set_ranker(|word, _| -(word.chars().count() as f64));
+        assert_eq!(
+            ac.predict_completions("for"),
+            ["fork", "form", "format", "formula"]
+        );
+
+        ac.clear_ranker();
+        assert_eq!(
+            ac.predict_completions("for"),
+            ["format", "formula", "form", "fork"]
+        );
+    }
+
+    #[test]
+    fn leaf_fast_path_matches_the_full_search() {
+        let ac = with_counts(Autocompleter::builder(), &[("apple", 4), ("app", 1)]);
+        assert_eq!(ac.predict_completions("apple"), ["apple"]);
+        assert_eq!(
+            ac.predict_completions("apple"),
+            ac.words_with_prefix("apple")
+        );
+        assert_eq!(
+            ac.predict_completions_with_counts("apple", 5),
+            [("apple".to_string(), 4)]
+        );
+
+        let mut cased = Autocompleter::builder().preserve_case(true).build();
+        cased.add_word("iPhone".to_string());
+        assert_eq!(
+            cased.predict_completions("iphone"),
+            cased.words_with_prefix("iphone")
+        );
+        assert_eq!(cased.predict_completions("iphone"), ["iPhone"]);
+
+        let mut excluding = Autocompleter::builder().exclude_exact_match(true).build();
+        excluding.add_word("apple".to_string());
+        assert!(excluding.predict_completions("apple").is_empty());
+        assert!(excluding.words_with_prefix("apple").is_empty());
+    }
+
+    #[test]
+    fn rename_moves_the_rank_to_the_new_spelling() {
+        let mut ac = with_counts(
+            Autocompleter::builder(),
+            &[("recieve", 5), ("receive", 2), ("recipe", 1)],
+        );
+
+        assert!(ac.rename("recieve", "receive"));
+        assert_eq!(ac.rank_of("receive"), Some(7));
+        assert!(!ac.contains("recieve"));
+        assert_eq!(ac.predict_completions("reci"), ["recipe"]);
+        assert!(ac.rename("recipe", "recipes"));
+        assert_eq!(ac.rank_of("recipes"), Some(1));
+        assert!(!ac.rename("missing", "found"));
+        assert!(!ac.contains("found"));
+        assert_eq!(ac.len(), 2);
+        ac.validate().unwrap();
+    }
+
+    #[test]
+    fn excluded_words_make_room_for_the_next_best() {
+        let ac = with_counts(
+            Autocompleter::builder(),
+            &[("hello", 5), ("help", 4), ("helmet", 3), ("held", 1)],
+        );
+        let exclude: HashSet<String> = HashSet::from(["hello".to_string()]);
+
+        assert_eq!(ac.predict_completions_n("hel", 2), ["hello", "help"]);
+        assert_eq!(
+            ac.predict_completions_excluding("hel", &exclude, 2),
+            ["help", "helmet"]
+        );
+        assert_eq!(
+            ac.predict_completions_excluding("hel", &HashSet::new(), 2),
+            ac.predict_completions_n("hel", 2)
+        );
+    }
+
+    #[test]
+    fn punctuation_and_spaces_are_ignored_when_matching() {
+        let mut ac = Autocompleter::builder()
+            .ignore_punctuation(true)
+            .case_insensitive(true)
+            .build();
+        ac.add_word("U.S.A".to_string());
+        ac.add_word("e-mail".to_string());
+        ac.add_word("email".to_string());
+        ac.add_word("e-mail".to_string());
+        ac.add_phrase("New York");
+
+        assert_eq!(ac.predict_completions("usa"), ["U.S.A"]);
+        assert_eq!(ac.predict_completions("u.s"), ["U.S.A"]);
+        assert_eq!(ac.predict_completions("ema"), ["e-mail"]);
+        assert_eq!(ac.rank_of("email"), Some(3));
+        assert_eq!(ac.predict_completions("newyork"), ["New York"]);
+        assert!(ac.contains("new-york"));
+    }
+
+    #[test]
+    fn exact_match_boost_puts_the_typed_word_first() {
+        let words = [("car", 1), ("cart", 5), ("carbon", 3)];
+        let boosted = with_counts(Autocompleter::builder().boost_exact_match(true), &words);
+        let plain = with_counts(Autocompleter::builder(), &words);
+
+        assert_eq!(
+            boosted.predict_completions("car"),
+            ["car", "cart", "carbon"]
+        );
+        assert_eq!(boosted.predict_completions_n("car", 2), ["car", "cart"]);
+        assert_eq!(plain.predict_completions("car"), ["cart", "carbon", "car"]);
+        // Without an exact match, ranking is unchanged
+        assert_eq!(
+            boosted.predict_completions("ca"),
+            plain.predict_completions("ca")
+        );
+    }
+
+    #[test]
+    fn load_errors_name_the_offending_line() {
+        let path = temp_file("bad-utf8.txt", b"apple\nbanana\nch\xffrry\ndate\n");
+        let err = Autocompleter::from_file(&path).err().unwrap();
+        assert!(err.starts_with("error on line 3: "), "{err}");
+
+        let mut skipped = Vec::new();
+        let mut ac = Autocompleter::new();
+        let loaded = ac.add_from_file_lenient(&path, |line, err| {
+            skipped.push((line, err.to_string()));
+        });
+        assert_eq!(loaded, Ok((3, 1)));
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].0, 3);
+        assert!(skipped[0].1.starts_with("error on line 3: "));
+        assert!(ac.contains("date"));
+        std::fs::remove_file(&path).unwrap();
+
+        let path = temp_file("bad-score.tsv", b"apple\t0.5\n\nbanana\tlots\n");
+        let err = Autocompleter::from_weighted_file_f64(&path).err().unwrap();
+        assert!(err.starts_with("error on line 3: invalid score"), "{err}");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn split_on_digits_breaks_words_read_from_files() {
+        let path = temp_file("digits.txt", b"item42name 2024\n");
+
+        let mut split = Autocompleter::builder().split_on_digits(true).build();
+        split.add_from_file(&path).unwrap();
+        assert!(split.contains("item"));
+        assert!(split.contains("name"));
+        assert!(!split.contains("item42name"));
+        // A token made only of digits leaves no word behind
+        assert_eq!(split.len(), 2);
+
+        let mut whole = Autocompleter::new();
+        whole.add_from_file(&path).unwrap();
+        assert!(whole.contains("item42name"));
+        assert!(!whole.contains("item"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn preserve_case_merges_casings_and_shows_the_usual_one() {
+        let mut ac = Autocompleter::builder().preserve_case(true).build();
+        ac.add_word("iPhone".to_string());
+        ac.add_word("iphone".to_string());
+        // Casings used equally often fall back to the one seen first
+        assert_eq!(
+            ac.predict_completions_with_counts("iph", 5),
+            [("iPhone".to_string(), 2)]
+        );
+        assert_eq!(ac.words().collect::<Vec<_>>(), ["iphone"]);
+
+        ac.add_word_with_count("IPHONE".to_string(), 3);
+        assert_eq!(ac.predict_completions("iph"), ["IPHONE"]);
+
+        assert!(ac.remove_word("iPhone"));
+        ac.add_word("iphone".to_string());
+        assert_eq!(ac.predict_completions("IPH"), ["iphone"]);
+    }
+
+    #[test]
+    fn remove_prefix_drops_the_whole_branch() {
+        let mut ac = with_counts(
+            Autocompleter::builder(),
+            &[("un", 1), ("undo", 4), ("unit", 2), ("up", 3), ("u", 1)],
+        );
+        assert_eq!(ac.remove_prefix("un"), 3);
+        assert_eq!(ac.len(), 2);
+        assert!(!ac.contains_prefix("un"));
+        assert_eq!(ac.predict_completions("u"), ["up", "u"]);
+        ac.validate().unwrap();
+
+        assert_eq!(ac.remove_prefix("zz"), 0);
+        assert_eq!(ac.remove_prefix(""), 2);
+        assert!(ac.is_empty());
+        ac.validate().unwrap();
+    }
+
+    #[test]
+    fn ranked_iter_yields_the_eager_ranking_lazily() {
+        let words: Vec<(String, i32)> = (0..300)
+            .map(|i| (format!("pre{i}"), 1 + i * 37 % 11))
+            .collect();
+        let words: Vec<(&str, i32)> = words.iter().map(|(w, c)| (w.as_str(), *c)).collect();
+
+        for builder in [
+            Autocompleter::builder(),
+            Autocompleter::builder().sort_order(SortOrder::AlphaThenFrequency),
+            Autocompleter::builder().boost_exact_match(true),
+        ] {
+            let mut ac = with_counts(builder, &words);
+            ac.add_word("pre".to_string());
+            let lazy: Vec<_> = ac.predict_ranked_iter("pre").take(10).collect();
+            assert_eq!(lazy, ac.predict_completions_with_counts("pre", 10));
+            assert_eq!(ac.predict_ranked_iter("pre").count(), 301);
+        }
+    }
+
+    #[test]
+    fn max_dfs_nodes_caps_the_work_on_a_large_subtree() {
+        let words: Vec<String> = ('a'..='z')
+            .flat_map(|x| ('a'..='z').map(move |y| format!("s{x}{y}")))
+            .collect();
+        let mut words: Vec<(&str, i32)> = words.iter().map(|w| (w.as_str(), 1)).collect();
+        words.push(("szz", 50));
+        words.push(("sab", 40));
+
+        let full = with_counts(Autocompleter::builder(), &words);
+        assert_eq!(full.predict_completions_with_counts("s", 1000).len(), 676);
+
+        let capped = with_counts(Autocompleter::builder().max_dfs_nodes(100), &words);
+        let found = capped.predict_completions_with_counts("s", 1000);
+        assert!(!found.is_empty() && found.len() < 100);
+        // The most popular branches are searched before the budget runs out
+        assert_eq!(found[0], ("szz".to_string(), 51));
+        assert_eq!(found[1], ("sab".to_string(), 41));
+        assert!(capped.predict_ranked_iter("s").count() < 100);
+    }
+
+    #[test]
+    fn borrowed_completions_match_the_owned_ones() {
+        let words: Vec<String> = (0..400).map(|i| format!("pre{}", i * 7 % 400)).collect();
+        let mut words: Vec<(&str, i32)> = words
+            .iter()
+            .enumerate()
+            .map(|(i, w)| (w.as_str(), 1 + (i % 13) as i32))
+            .collect();
+        words.push(("pre", 1));
+        words.push(("Prefix", 2));
+
+        for builder in [
+            Autocompleter::builder(),
+            Autocompleter::builder().boost_exact_match(true),
+            Autocompleter::builder().max_dfs_nodes(50),
+            Autocompleter::builder()
+                .boost_exact_match(true)
+                .max_dfs_nodes(50),
+            Autocompleter::builder().preserve_case(true),
+            Autocompleter::builder().sort_order(SortOrder::AlphaOnly),
+            Autocompleter::builder().exclude_exact_match(true),
+            Autocompleter::builder().store_words(false),
+        ] {
+            let ac = with_counts(builder, &words);
+            for prefix in ["p", "pre", "pre1", "prefix", "q"] {
+                for limit in [0, 1, 10] {
+                    let borrowed: Vec<String> = ac
+                        .predict_completions_ref(prefix, limit)
+                        .into_iter()
+                        .map(Cow::into_owned)
+                        .collect();
+                    assert_eq!(borrowed, ac.predict_completions_n(prefix, limit));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn merge_from_file_keeps_what_add_from_file_keeps() {
+        let path = temp_file(
+            "merge-cased.txt",
+            b"iPhone iphone iPhone iPad\nMacBook macbook\n",
+        );
+        let mut added = Autocompleter::builder().preserve_case(true).build();
+        added.add_from_file(&path).unwrap();
+        let mut merged = Autocompleter::builder().preserve_case(true).build();
+        assert_eq!(merged.merge_from_file(&path), Ok(3));
+        for prefix in ["i", "IP", "mac"] {
+            assert_eq!(
+                merged.predict_completions_with_counts(prefix, 10),
+                added.predict_completions_with_counts(prefix, 10)
+            );
+        }
+        assert_eq!(merged.predict_completions("iph"), ["iPhone"]);
+
+        // Merged words go to the log, and come back when it's replayed
+        let dict = temp_file("merge-dict.txt", b"apple\n");
+        let log = temp_path("merge-log.txt");
+        let mut logged = Autocompleter::open_with_log(&dict, &log).unwrap();
+        logged.merge_from_file(&path).unwrap();
+        drop(logged);
+        let replayed = Autocompleter::open_with_log(&dict, &log).unwrap();
+        assert_eq!(replayed.rank_of("iPhone"), Some(2));
+        assert_eq!(replayed.rank_of("iphone"), Some(1));
+        assert_eq!(replayed.rank_of("macbook"), Some(1));
+        assert_eq!(replayed.rank_of("apple"), Some(1));
+        for file in [path, dict, log] {
+            std::fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn dedup_folded_merges_casings_in_every_mode() {
+        let words = [("Color", 3), ("color", 1), ("colour", 1), ("Cold", 1)];
+
+        // Case sensitive storage merges at query time, from any casing of the prefix
+        let plain = with_counts(Autocompleter::builder(), &words);
+        assert_eq!(plain.predict_completions("co"), ["color", "colour"]);
+        let merged = with_counts(Autocompleter::builder().dedup_folded(true), &words);
+        let expected = [
+            ("Color".to_string(), 4),
+            ("Cold".to_string(), 1),
+            ("colour".to_string(), 1),
+        ];
+        assert_eq!(merged.predict_completions_with_counts("co", 10), expected);
+        assert_eq!(merged.predict_completions_with_counts("CO", 10), expected);
+        assert_eq!(merged.predict_completions_n("co", 1), ["Color"]);
+        assert_eq!(merged.predict_completions_ref("co", 2), ["Color", "Cold"]);
+        assert_eq!(merged.len(), 4);
+
+        // Case insensitive storage merges when adding, and now shows the usual spelling
+        let folded = with_counts(Autocompleter::builder().case_insensitive(true), &words);
+        assert_eq!(
+            folded.predict_completions("co"),
+            ["color", "cold", "colour"]
+        );
+        let folded = with_counts(
+            Autocompleter::builder()
+                .case_insensitive(true)
+                .dedup_folded(true),
+            &words,
+        );
+        assert_eq!(folded.predict_completions_with_counts("co", 10), expected);
+
+        // Punctuation is stripped first, then casings merge
+        let dotted = with_counts(
+            Autocompleter::builder()
+                .ignore_punctuation(true)
+                .dedup_folded(true),
+            &[("U.S.A", 2), ("usa", 1), ("us", 1)],
+        );
+        assert_eq!(
+            dotted.predict_completions_with_counts("u.s", 10),
+            [("U.S.A".to_string(), 3), ("us".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn max_len_counts_the_folded_prefix() {
+        let dotted = with_counts(
+            Autocompleter::builder().ignore_punctuation(true),
+            &[("U.S.A", 2), ("U.S.S.R", 1)],
+        );
+        assert_eq!(dotted.predict_completions_max_len("U.S.", 3, 10), ["U.S.A"]);
+        assert_eq!(
+            dotted.predict_completions_max_len("U.S.", 4, 10),
+            ["U.S.A", "U.S.S.R"]
+        );
+        assert!(dotted
+            .predict_completions_max_len("U.S.S.", 3, 10)
+            .is_empty());
+
+        let mut spaced = Autocompleter::builder().normalize_whitespace(true).build();
+        spaced.add_phrase("new york");
+        spaced.add_phrase("new yorker");
+        assert_eq!(
+            spaced.predict_completions_max_len("new    y", 8, 10),
+            ["new york"]
+        );
+    }
+
+    #[test]
+    fn predicted_words_survive_eviction() {
+        let mut ac = Autocompleter::builder()
+            .max_words(3)
+            .eviction_policy(EvictionPolicy::LeastRecentlyUsed)
+            .build();
+        ac.add_words(["one", "two", "three"].map(String::from));
+        // Being predicted counts as a use, so "one" outlives "two" and "three"
+        assert_eq!(ac.predict_completions("on"), ["one"]);
+        assert_eq!(ac.predict_completions_ref("on", 10), ["one"]);
+        ac.add_words(["four", "five"].map(String::from));
+
+        let mut words: Vec<String> = ac.words().map(String::from).collect();
+        words.sort();
+        assert_eq!(words, ["five", "four", "one"]);
+        ac.validate().unwrap();
+
+        // Looking a word up doesn't count as a use, so "one" is now the oldest
+        assert!(ac.contains("one"));
+        ac.add_word("six".to_string());
+        assert!(!ac.contains("one"));
+        assert!(ac.contains("four"));
+        ac.validate().unwrap();
+    }
+
+    #[test]
+    fn failed_loads_can_be_retried() {
+        let path = temp_file("retry.txt", b"alpha\n\xFF\n");
+        let mut ac = Autocompleter::new();
+        assert!(ac.add_from_file(&path).is_err());
+
+        // The failed load wasn't recorded, so the fixed file still loads
+        std::fs::write(&path, "beta\n").unwrap();
+        ac.add_from_file(&path).unwrap();
+        assert!(ac.contains("beta"));
+        assert!(ac.add_from_file(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn phrase_files_are_only_loaded_once() {
+        let path = temp_file("phrases-once.txt", b"machine learning\n");
+        let mut ac = Autocompleter::new();
+        ac.add_phrases_from_file(&path).unwrap();
+        assert!(ac.add_phrases_from_file(&path).is_err());
+        assert_eq!(ac.rank_of("machine learning"), Some(1));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn from_file_with_records_the_file() {
+        let path = temp_file("tokenized-once.txt", b"a,b\n");
+        let mut ac = Autocompleter::from_file_with(&path, |line| {
+            line.split(',').map(String::from).collect()
+        })
+        .unwrap();
+        assert!(ac.add_from_file(&path).is_err());
+        assert_eq!(ac.rank_of("a"), Some(1));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,474 @@
+use std::collections::HashSet;
+
+use super::mwt::Mwt;
+use super::{Autocompleter, EvictionPolicy, Settings, SortOrder, TieBreak, WordLog};
+
+/// Builder used to construct an `Autocompleter` with non-default settings.
+///
+/// Every setting starts out at the value `Autocompleter::new` would use, so only
+/// the options that matter to the caller need to be set.
+///
+/// # Fields
+///
+/// `settings` (`Settings`) - Options that will be handed to the built `Autocompleter`.
+#[derive(Debug, Default, Clone)]
+pub struct AutocompleterBuilder {
+    settings: Settings,
+}
+
+impl AutocompleterBuilder {
+    /// Constructs a new `AutocompleterBuilder` with the default settings.
+    pub fn new() -> AutocompleterBuilder {
+        AutocompleterBuilder {
+            settings: Settings::default(),
+        }
+    }
+
+    /// Constructs a new `AutocompleterBuilder` starting from existing settings.
+    pub(super) fn from_settings(settings: Settings) -> AutocompleterBuilder {
+        AutocompleterBuilder { settings }
+    }
+
+    /// Sets the ordering used for prediction results.
+    ///
+    /// # Arguments
+    ///
+    /// `order` (`SortOrder`) - Ordering to apply. Defaults to `SortOrder::FrequencyThenAlpha`.
+    pub fn sort_order(mut self, order: SortOrder) -> AutocompleterBuilder {
+        self.settings.sort_order = order;
+        self
+    }
+
+    /// Sets how completions with the same frequency are ordered under
+    /// `SortOrder::FrequencyThenAlpha`, e.g. `TieBreak::ShorterFirst` to
+    /// suggest "cat" before "catalog" when both are equally common. Has no
+    /// effect with the alphabetical sort orders.
+    ///
+    /// # Arguments
+    ///
+    /// `tie_break` (`TieBreak`) - Tie break to apply. Defaults to `TieBreak::Alphabetical`.
+    pub fn tie_break(mut self, tie_break: TieBreak) -> AutocompleterBuilder {
+        self.settings.tie_break = tie_break;
+        self
+    }
+
+    /// Enables recency-weighted ranking.
+    ///
+    /// Every insertion into the `Autocompleter` multiplies each word's score by
+    /// `decay` before adding 1 to the inserted word's score, and predictions
+    /// are ranked by that score instead of the raw frequency. A word inserted
+    /// often but long ago can therefore be overtaken by a word inserted a few
+    /// times recently. Off by default.
+    ///
+    /// # Arguments
+    ///
+    /// `decay` (`f64`) - Per-insertion decay factor, in the range `(0, 1]`. A value of
+    /// 1 means no decay, which ranks the same as plain frequency.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `decay` is outside of `(0, 1]`.
+    pub fn recency_decay(mut self, decay: f64) -> AutocompleterBuilder {
+        assert!(
+            decay > 0.0 && decay <= 1.0,
+            "recency decay must be in the range (0, 1], got {decay}"
+        );
+        self.settings.recency_decay = Some(decay);
+        self
+    }
+
+    /// Enables Porter stemming of inserted words.
+    ///
+    /// Every word added through `add_word`, `add_words`, `add_word_with_count`
+    /// or a file loader is reduced to its English stem first, so variants such
+    /// as "run", "running" and "runs" accumulate rank on the single entry
+    /// "run". The original surface forms are *not* kept: predictions return
+    /// the stems. Prefixes passed to predictions are used as-is. Off by default.
+    ///
+    /// # Arguments
+    ///
+    /// `stem` (`bool`) - Whether to stem inserted words.
+    #[cfg(feature = "stemming")]
+    pub fn stem(mut self, stem: bool) -> AutocompleterBuilder {
+        self.settings.stem = stem;
+        self
+    }
+
+    /// Caps the number of distinct words the `Autocompleter` will store, to
+    /// bound memory use on untrusted or unbounded input.
+    ///
+    /// Once the cap is reached, inserting a word that isn't already stored is
+    /// handled according to the `eviction_policy`, which by default silently
+    /// rejects it. Words that are already stored always still have their rank
+    /// increased. No cap by default.
+    ///
+    /// # Arguments
+    ///
+    /// `max_words` (`usize`) - Maximum number of distinct words.
+    pub fn max_words(mut self, max_words: usize) -> AutocompleterBuilder {
+        self.settings.max_words = Some(max_words);
+        self
+    }
+
+    /// Sets what happens when a new word arrives once the `max_words` cap has
+    /// been reached. Has no effect without a cap.
+    ///
+    /// With `EvictionPolicy::LeastRecentlyUsed`, the word whose last use is
+    /// the oldest is removed to make room, which turns the `Autocompleter`
+    /// into a tracker of the most recent vocabulary of a stream. Adding a
+    /// stored word again in any way counts as a use, and so does being
+    /// returned by a prediction, so words people pick keep their place.
+    ///
+    /// # Arguments
+    ///
+    /// `policy` (`EvictionPolicy`) - Policy to apply. Defaults to `EvictionPolicy::RejectNew`.
+    pub fn eviction_policy(mut self, policy: EvictionPolicy) -> AutocompleterBuilder {
+        self.settings.eviction = policy;
+        self
+    }
+
+    /// Leaves the prefix itself out of its completions when it is a stored
+    /// word, so only strictly longer words are suggested. For example with
+    /// "car" and "card" stored, predicting "car" returns just "card". Off by
+    /// default.
+    ///
+    /// # Arguments
+    ///
+    /// `exclude` (`bool`) - Whether to drop the exact match.
+    pub fn exclude_exact_match(mut self, exclude: bool) -> AutocompleterBuilder {
+        self.settings.exclude_exact_match = exclude;
+        self
+    }
+
+    /// Puts the prefix itself first among its completions when it is a
+    /// stored word, however rarely it was seen, since it's literally what
+    /// was typed. The other completions follow in their usual order. For
+    /// example with "car" seen once and "card" seen five times, predicting
+    /// "car" returns "car" before "card". Applies to `predict_completions`
+    /// and the methods that rank like `predict_completions_n`. Has no effect
+    /// together with `exclude_exact_match`, which leaves the prefix out
+    /// entirely. Off by default, keeping a pure frequency order.
+    ///
+    /// # Arguments
+    ///
+    /// `boost` (`bool`) - Whether to put the exact match first.
+    pub fn boost_exact_match(mut self, boost: bool) -> AutocompleterBuilder {
+        self.settings.boost_exact_match = boost;
+        self
+    }
+
+    /// Sets punctuation characters that should be kept at the end of words
+    /// read from a file, rather than trimmed off like the rest of ASCII
+    /// punctuation. For example keeping `'#'` loads "C#" intact instead of as
+    /// "C". Nothing is kept by default.
+    ///
+    /// # Arguments
+    ///
+    /// `chars` (`I: IntoIterator<Item = char>`) - Characters to keep.
+    pub fn keep_chars<I: IntoIterator<Item = char>>(mut self, chars: I) -> AutocompleterBuilder {
+        self.settings.keep_chars = chars.into_iter().collect();
+        self
+    }
+
+    /// Treats digits in words read from a file as word boundaries, so
+    /// "item42name" loads as "item" and "name" rather than as one word.
+    /// Numbers on their own, such as "2024", are dropped entirely. Trailing
+    /// punctuation is trimmed from each piece after splitting. Words added
+    /// directly, e.g. with `Autocompleter::add_word`, are never split. Off by
+    /// default.
+    ///
+    /// Any character `char::is_numeric` accepts counts as a digit, not only
+    /// ASCII ones.
+    ///
+    /// # Arguments
+    ///
+    /// `split` (`bool`) - Whether to split words on digits.
+    pub fn split_on_digits(mut self, split: bool) -> AutocompleterBuilder {
+        self.settings.split_on_digits = split;
+        self
+    }
+
+    /// Sets how many characters a prefix needs before any completions are
+    /// offered, e.g. 3 to avoid huge result sets after a single keystroke.
+    /// Shorter prefixes get no completions. Defaults to 1.
+    ///
+    /// # Arguments
+    ///
+    /// `len` (`usize`) - Minimum prefix length, in characters.
+    pub fn min_prefix_len(mut self, len: usize) -> AutocompleterBuilder {
+        self.settings.min_prefix_len = Some(len);
+        self
+    }
+
+    /// Makes searches visit each node's children in character order instead
+    /// of hash map order, so the order results are collected in is the same
+    /// from run to run even before they are ranked. Final rankings are the
+    /// same either way; this only helps when debugging the collection step.
+    /// Off by default, since sorting children costs time on every search.
+    ///
+    /// Building with the `deterministic` feature turns this on for every
+    /// `Autocompleter`, whatever is passed here. Each visited node then
+    /// collects and sorts its children before descending, which adds an
+    /// allocation and a sort per node; for typical dictionaries, where most
+    /// nodes have only a few children, that is a small fraction of a search.
+    ///
+    /// # Arguments
+    ///
+    /// `sorted` (`bool`) - Whether to visit children in sorted order.
+    pub fn sorted_traversal(mut self, sorted: bool) -> AutocompleterBuilder {
+        self.settings.sorted_traversal = sorted;
+        self
+    }
+
+    /// Makes the `Autocompleter` ignore case. Inserted words and every word or
+    /// prefix passed to a query are folded with `str::to_lowercase`, the
+    /// same way in both directions, so "Rust" and "rust" are one entry that
+    /// any casing of the prefix finds. Predictions return the lowercased
+    /// form. Off by default.
+    ///
+    /// `to_lowercase` applies the default Unicode lowercase mapping, with no
+    /// locale rules and no full case folding. In particular "İstanbul" becomes
+    /// "i̇stanbul" (with a combining dot) and so doesn't match "istanbul", and
+    /// "STRASSE" becomes "strasse", which doesn't match "straße".
+    ///
+    /// # Arguments
+    ///
+    /// `insensitive` (`bool`) - Whether to ignore case.
+    pub fn case_insensitive(mut self, insensitive: bool) -> AutocompleterBuilder {
+        self.settings.case_insensitive = insensitive;
+        self
+    }
+
+    /// Matches words regardless of case, like `case_insensitive`, but keeps
+    /// the casing they were added with for display, so "iph" finds "iPhone"
+    /// and predictions show "iPhone" rather than "iphone". Off by default.
+    ///
+    /// Words and queries are lowercased the same way `case_insensitive` does
+    /// it, so words that differ only in case, such as "iPhone" and "iphone",
+    /// are still one entry and their ranks add up. Predictions show the
+    /// casing the word was added with most often, and of casings used equally
+    /// often, the one seen first. `words`, `to_vec` and the exporters report the
+    /// lowercased form that is actually stored.
+    ///
+    /// Every added word keeps each casing it was added with, and how often,
+    /// in its node, so this costs at least one extra string per stored word.
+    /// They go when the word is removed or evicted, so a word added again
+    /// afterwards starts counting afresh.
+    ///
+    /// # Arguments
+    ///
+    /// `preserve` (`bool`) - Whether to ignore case while keeping display casing.
+    pub fn preserve_case(mut self, preserve: bool) -> AutocompleterBuilder {
+        self.settings.preserve_case = preserve;
+        self
+    }
+
+    /// Collapses every run of whitespace in inserted words and in every word
+    /// or prefix passed to a query into a single space, the same way in both
+    /// directions, so input like "new   york" finds the stored phrase
+    /// "new york". Whitespace at either end is collapsed but not removed, so a
+    /// prefix ending in a space still only matches phrases. Off by default.
+    ///
+    /// # Arguments
+    ///
+    /// `normalize` (`bool`) - Whether to collapse whitespace.
+    pub fn normalize_whitespace(mut self, normalize: bool) -> AutocompleterBuilder {
+        self.settings.normalize_whitespace = normalize;
+        self
+    }
+
+    /// Strips ASCII punctuation and all whitespace from inserted words and
+    /// from every word or prefix passed to a query, so "U.S.A" is stored
+    /// under "USA" and "e-mail" and "email" are the same word. Predictions
+    /// still return each word the way it was added most often, e.g. "U.S.A"
+    /// rather than "USA", with ties going to the spelling seen first, while
+    /// `words`, `to_vec` and the exporters report the stripped form that is
+    /// actually stored. Combine with `case_insensitive` for "usa" to find
+    /// "U.S.A". Off by default.
+    ///
+    /// Only ASCII punctuation is stripped, so typographic characters such as
+    /// "’" or "–" still have to match exactly.
+    ///
+    /// # Arguments
+    ///
+    /// `ignore` (`bool`) - Whether to strip punctuation and whitespace.
+    pub fn ignore_punctuation(mut self, ignore: bool) -> AutocompleterBuilder {
+        self.settings.ignore_punctuation = ignore;
+        self
+    }
+
+    /// Merges completions that only differ in case, such as "Color" and
+    /// "color", into one, ranked by their summed ranks and shown in the
+    /// spelling with the highest rank. Off by default.
+    ///
+    /// With `case_insensitive`, words that differ in case are already one
+    /// entry, but predictions show the lowercased form. This makes every
+    /// added word also keep the spellings it was added with, as
+    /// `preserve_case` does, so predictions show the most used one instead.
+    ///
+    /// Without any case folding, words are still stored with their case, so
+    /// the merge happens at query time: the prefix matches every stored
+    /// casing of itself, so "co" finds "Color" as well as "color", and the
+    /// completions are merged by their lowercased form. This costs a full
+    /// search of every matching branch, like a non-default `SortOrder`.
+    /// Casings used equally often go to the one that sorts first. The merge
+    /// applies to `predict_completions` and the predictions that pick the top
+    /// completions the same way, such as `predict_completions_n`,
+    /// `predict_completions_with_counts` and `predict_scored`.
+    /// `predict_completions_ref` then returns owned strings, since merged
+    /// completions don't exist in the MWT to borrow from.
+    ///
+    /// # Arguments
+    ///
+    /// `dedup` (`bool`) - Whether to merge completions that only differ in case.
+    pub fn dedup_folded(mut self, dedup: bool) -> AutocompleterBuilder {
+        self.settings.dedup_folded = dedup;
+        self
+    }
+
+    /// Makes `add_word_with_source` record which source each word came from,
+    /// for `predict_with_sources`. Off by default, in which case no node
+    /// carries any source storage.
+    ///
+    /// # Arguments
+    ///
+    /// `track` (`bool`) - Whether to record sources.
+    pub fn track_sources(mut self, track: bool) -> AutocompleterBuilder {
+        self.settings.track_sources = track;
+        self
+    }
+
+    /// Keeps user-perceived characters whole when matching prefixes. The MWT
+    /// is keyed on Unicode scalar values, so a grapheme cluster such as "é"
+    /// written as "e" plus a combining accent, or a flag emoji made of two
+    /// regional indicators, spans several levels, and a prefix ending partway
+    /// through one would otherwise match. With this on, the prediction
+    /// methods leave out any completion whose grapheme cluster straddles the
+    /// end of the prefix, so "e" no longer completes to "école" spelled with a
+    /// combining accent. Off by default.
+    ///
+    /// Nodes stay keyed on `char` rather than on grapheme cluster strings.
+    /// String keys would cost a heap allocation for every node's key on top
+    /// of the node itself, while this check only costs a little time per
+    /// completion. Frequency ranked
+    /// predictions can't skip branches early in this mode, though.
+    ///
+    /// # Arguments
+    ///
+    /// `graphemes` (`bool`) - Whether to keep grapheme clusters whole.
+    #[cfg(feature = "unicode-segmentation")]
+    pub fn graphemes(mut self, graphemes: bool) -> AutocompleterBuilder {
+        self.settings.graphemes = graphemes;
+        self
+    }
+
+    /// Makes the file loaders count, for every word, how many distinct lines it
+    /// appears on, treating each line as a document. The count is read back
+    /// with `Autocompleter::doc_freq_of`. Off by default, since it costs a set
+    /// of the words seen on each line while loading.
+    ///
+    /// # Arguments
+    ///
+    /// `track` (`bool`) - Whether to count document frequencies.
+    pub fn track_doc_freq(mut self, track: bool) -> AutocompleterBuilder {
+        self.settings.track_doc_freq = track;
+        self
+    }
+
+    /// Ranks predictions by each word's float score, as loaded by
+    /// `Autocompleter::add_from_weighted_file_f64` or added with
+    /// `add_word_with_score`, instead of by how often the word was added.
+    /// Higher scores come first, so log-likelihoods work as they are. Words
+    /// without a score count as 0. This replaces frequency and recency
+    /// ranking, but a custom ranker still takes precedence. Off by default.
+    ///
+    /// # Arguments
+    ///
+    /// `float_scores` (`bool`) - Whether to rank by float score.
+    pub fn float_scores(mut self, float_scores: bool) -> AutocompleterBuilder {
+        self.settings.float_scores = float_scores;
+        self
+    }
+
+    /// Stops word nodes from keeping their own copy of the word. Every word
+    /// is already spelled out by the path of characters leading to its node,
+    /// so searches rebuild words from the path as they descend instead. This
+    /// saves the heap allocation each stored word otherwise needs, at the
+    /// cost of copying characters during searches that would otherwise only
+    /// have been borrowed. Words are stored by default.
+    ///
+    /// `Autocompleter::words` and `predict_completions_ref` still work, but
+    /// hand out owned strings, and `MwtNode::get_data` returns "" for every
+    /// node.
+    ///
+    /// Nodes still have room for a copy, so this only saves the words' own
+    /// bytes. Building without the default `store-words` feature removes the
+    /// field from every node, which saves far more, and words are then never
+    /// stored whatever this is set to.
+    ///
+    /// # Arguments
+    ///
+    /// `store` (`bool`) - Whether word nodes keep a copy of their word.
+    pub fn store_words(mut self, store: bool) -> AutocompleterBuilder {
+        self.settings.store_words = Some(store);
+        self
+    }
+
+    /// Skips any word longer than `max_word_len` characters, as a guard
+    /// against malformed or hostile input where a single "word" millions of
+    /// characters long would otherwise cost a node per character. Over-long
+    /// words are dropped silently, whether they come from a file or are added
+    /// directly. No limit by default.
+    ///
+    /// # Arguments
+    ///
+    /// `max_word_len` (`usize`) - Longest word to store, in characters.
+    pub fn max_word_len(mut self, max_word_len: usize) -> AutocompleterBuilder {
+        self.settings.max_word_len = Some(max_word_len);
+        self
+    }
+
+    /// Caps how many MWT nodes a single prediction search may visit, to
+    /// bound latency when a short prefix leads to a huge part of the
+    /// dictionary. Once the cap is reached the search stops and the words
+    /// found so far are ranked and returned as usual. No limit by default.
+    ///
+    /// Results are approximate whenever the cap is reached: a popular word
+    /// in a branch that wasn't visited is missing, and a less popular one
+    /// takes its place. Branches are searched in order of the best rank
+    /// below them, so the most popular words are usually found first. Whole
+    /// dictionary queries such as `most_popular`, `words` and the exporters
+    /// aren't capped.
+    ///
+    /// # Arguments
+    ///
+    /// `max_nodes` (`usize`) - Most nodes one search visits, counting the
+    /// node the prefix leads to.
+    pub fn max_dfs_nodes(mut self, max_nodes: usize) -> AutocompleterBuilder {
+        self.settings.max_dfs_nodes = Some(max_nodes);
+        self
+    }
+
+    /// Consumes the builder and constructs an empty `Autocompleter` with the
+    /// chosen settings.
+    pub fn build(self) -> Autocompleter {
+        self.build_with_payloads()
+    }
+
+    /// Consumes the builder and constructs an empty `Autocompleter` with the
+    /// chosen settings, whose words can carry a payload of type `T` added
+    /// with `Autocompleter::add_word_with_payload`.
+    pub fn build_with_payloads<T>(self) -> Autocompleter<T> {
+        let mut trie = Mwt::new();
+        trie.set_max_words(self.settings.max_words);
+        trie.set_eviction(self.settings.eviction);
+        trie.set_max_word_len(self.settings.max_word_len);
+        trie.set_store_words(self.settings.store_words.unwrap_or(true));
+        Autocompleter {
+            trie,
+            settings: self.settings,
+            loaded_files: HashSet::new(),
+            log: WordLog::default(),
+        }
+    }
+}
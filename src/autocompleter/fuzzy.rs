@@ -0,0 +1,89 @@
+use super::mwt::MwtNode;
+
+/// Visits every node of the MWT whose path from the root is within
+/// `max_distance` edits (insertions, deletions or substitutions) of `target`.
+///
+/// This is the classic Levenshtein trie search: one row of the edit distance
+/// table is computed per node, building on its parent's row, and any branch
+/// whose row has no entry within `max_distance` is skipped entirely.
+///
+/// # Arguments
+///
+/// * `root` (`&MwtNode`) - Node to start from, normally the root of the MWT
+/// * `target` (`&[char]`) - Characters of the string being matched against
+/// * `max_distance` (`usize`) - Largest edit distance that counts as a match
+/// * `visit` (`F: FnMut(&MwtNode, &str, usize) -> bool`) - Called with each matching node,
+///   its path from the root and its distance. Returning `true` stops the search early.
+///
+/// # Return value
+///
+/// `true` if the search was stopped early by `visit`.
+pub fn visit_within<'a, T, F>(
+    root: &'a MwtNode<T>,
+    target: &[char],
+    max_distance: usize,
+    visit: &mut F,
+) -> bool
+where
+    F: FnMut(&'a MwtNode<T>, &str, usize) -> bool,
+{
+    // Distance from the empty path to each prefix of the target.
+    let row: Vec<usize> = (0..=target.len()).collect();
+    if row[target.len()] <= max_distance && visit(root, "", row[target.len()]) {
+        return true;
+    }
+
+    // Each entry holds the length of its parent's path, the character leading
+    // to it and its own edit distance row. The walk is iterative, so very deep
+    // tries can't overflow the stack.
+    let mut path = String::new();
+    let mut stack: Vec<(usize, char, &MwtNode<T>, Vec<usize>)> = Vec::new();
+    push_children(root, target, &row, max_distance, 0, &mut stack);
+    while let Some((len, ch, node, row)) = stack.pop() {
+        path.truncate(len);
+        path.push(ch);
+        let distance = row[target.len()];
+        if distance <= max_distance && visit(node, &path, distance) {
+            return true;
+        }
+        push_children(node, target, &row, max_distance, path.len(), &mut stack);
+    }
+    false
+}
+
+/// Helper for `visit_within`. Computes the edit distance row for each child
+/// of `node` from `row`, and pushes the child to be visited while a match is
+/// still possible below it.
+///
+/// # Arguments
+///
+/// * `node` (`&MwtNode`) - Node whose children are being examined
+/// * `target` (`&[char]`) - Characters of the string being matched against
+/// * `row` (`&[usize]`) - Edit distance row of `node`
+/// * `max_distance` (`usize`) - Largest edit distance that counts as a match
+/// * `len` (`usize`) - Length of the path leading from the root to `node`
+/// * `stack` (`&mut Vec<(usize, char, &MwtNode, Vec<usize>)>`) - Nodes still to be visited
+fn push_children<'a, T>(
+    node: &'a MwtNode<T>,
+    target: &[char],
+    row: &[usize],
+    max_distance: usize,
+    len: usize,
+    stack: &mut Vec<(usize, char, &'a MwtNode<T>, Vec<usize>)>,
+) {
+    for (ch, child) in node.children_iter() {
+        let mut next_row = Vec::with_capacity(row.len());
+        next_row.push(row[0] + 1);
+        for (idx, target_ch) in target.iter().enumerate() {
+            let substitution = row[idx] + usize::from(*target_ch != ch);
+            let insertion = next_row[idx] + 1;
+            let deletion = row[idx + 1] + 1;
+            next_row.push(substitution.min(insertion).min(deletion));
+        }
+
+        // Only keep going down this branch if some alignment is still close enough.
+        if next_row.iter().any(|d| *d <= max_distance) {
+            stack.push((len, ch, child, next_row));
+        }
+    }
+}
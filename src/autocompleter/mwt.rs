@@ -1,34 +1,99 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::mem::size_of;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::EvictionPolicy;
+
+// Most children `Mwt::reserve` will make room for at the root, which roughly covers
+// the letters, digits and accented letters of a typical word list
+const MAX_ROOT_RESERVE: usize = 128;
 
 /// Type alias for ease of use.
-type HeapMap = Box<HashMap<char, Option<Box<MwtNode>>>>;
+type HeapMap<T> = Box<HashMap<char, Option<Box<MwtNode<T>>>>>;
+
+/// Source identifiers recorded for a word, boxed so an unused set only costs
+/// a pointer.
+type SourceSet = Box<BTreeSet<u64>>;
+
+/// Spellings a word was added with and how often each was used, most used
+/// first. Boxed for the same reason as `SourceSet`.
+type SurfaceList = Box<Vec<(Box<str>, i32)>>;
 
 /// Implementation of an individual node that makes up the MWT.
 ///
 /// # Fields
 ///
 /// * `is_end` (`bool`) - Indicates if a node holds a completed word
-/// * `data` (`String`) - The word stored in this node, or ""
+/// * `data` (`String`) - The word stored in this node, or "". Always "" when the `Mwt`
+///   doesn't store words, in which case words are rebuilt from their paths. Only present
+///   with the `store-words` feature, so nodes are smaller without it
 /// * `rank` (`i32`) - How many times this word appears in the dataset/is inserted
 /// * `children` (`HeapMap`) - Mapping from character to `MwtNode`. For each character in inserted
-/// words, we make an entry here.
+///   words, we make an entry here.
+/// * `last_seen` (`u64`) - Value of the `Mwt` insertion clock when this word was last inserted
+/// * `recency` (`f64`) - Time-decayed insertion count, as of `last_seen`. Only maintained by
+///   `Mwt::add_record_decayed`.
+/// * `last_used` (`AtomicU64`) - Value of the `Mwt` use clock when this word was last inserted
+///   or returned by a prediction. Atomic so predictions can refresh it through a shared
+///   reference. Only maintained under `EvictionPolicy::LeastRecentlyUsed`
+/// * `queued_at` (`u64`) - Use clock value the word is filed under in the eviction order,
+///   which trails `last_used` until eviction reaches the word and files it again
+/// * `max_subtree_rank` (`i32`) - Upper bound on the rank of any word at or below this node,
+///   used to prune searches for the most frequent words
+/// * `subtree_word_count` (`usize`) - Number of words stored at or below this node
+/// * `doc_freq` (`u32`) - Number of distinct lines the word was read from by a file loader.
+///   Only maintained when document frequencies are tracked
+/// * `sources` (`Option<SourceSet>`) - Identifiers of the sources the word was seen in.
+///   Only allocated once a source is recorded, so nodes stay small when sources aren't tracked
+/// * `score` (`f64`) - Sum of the float scores the word was loaded with. Only maintained by
+///   `Mwt::add_record_with_score`
+/// * `payload` (`Option<T>`) - Caller data attached to the word by `Mwt::add_record_with_payload`
+/// * `surfaces` (`Option<SurfaceList>`) - Spellings the word was added with, when
+///   `ignore_punctuation` or `preserve_case` store it under a folded form. Set by
+///   `Mwt::record_surface`
 ///
-/// The definition of `HeapMap` is given above.
-pub struct MwtNode {
+/// The definitions of `HeapMap`, `SourceSet` and `SurfaceList` are given above.
+pub struct MwtNode<T = ()> {
     is_end: bool,
+    #[cfg(feature = "store-words")]
     data: String,
     rank: i32,
-    children: HeapMap,
+    children: HeapMap<T>,
+    last_seen: u64,
+    recency: f64,
+    last_used: AtomicU64,
+    queued_at: u64,
+    max_subtree_rank: i32,
+    subtree_word_count: usize,
+    doc_freq: u32,
+    sources: Option<SourceSet>,
+    score: f64,
+    payload: Option<T>,
+    surfaces: Option<SurfaceList>,
 }
 
-impl MwtNode {
+impl<T> MwtNode<T> {
     /// Constructs an new, empty MwtNode.
-    fn new() -> MwtNode {
+    fn new() -> MwtNode<T> {
         MwtNode {
             is_end: false,
+            #[cfg(feature = "store-words")]
             data: String::new(),
             rank: 0,
             children: Box::new(HashMap::new()),
+            last_seen: 0,
+            recency: 0.0,
+            last_used: AtomicU64::new(0),
+            queued_at: 0,
+            max_subtree_rank: 0,
+            subtree_word_count: 0,
+            doc_freq: 0,
+            sources: None,
+            score: 0.0,
+            payload: None,
+            surfaces: None,
         }
     }
 
@@ -36,11 +101,28 @@ impl MwtNode {
     ///
     /// # Return value
     ///
-    /// Reference to the `data` field of the given `MwtNode`.
-    pub fn get_data(&self) -> &String {
+    /// Reference to the `data` field of the given `MwtNode`. Empty when the
+    /// `Autocompleter` was built with `store_words(false)`, in which case the
+    /// word is the path of characters leading to this node. Only available
+    /// with the `store-words` feature.
+    #[cfg(feature = "store-words")]
+    pub fn get_data(&self) -> &str {
         &self.data
     }
 
+    /// The node's own copy of its word, for code that has to work with or
+    /// without the `store-words` feature.
+    ///
+    /// # Return value
+    ///
+    /// The `data` field, or "" if words aren't stored or the feature is off.
+    pub(super) fn stored_word(&self) -> &str {
+        #[cfg(feature = "store-words")]
+        return &self.data;
+        #[cfg(not(feature = "store-words"))]
+        return "";
+    }
+
     /// Accessor method for the count of appearances of a finished word.
     ///
     /// # Return value
@@ -50,6 +132,88 @@ impl MwtNode {
         self.rank
     }
 
+    /// Accessor method for the highest rank found at or below a `MwtNode`.
+    ///
+    /// This is an upper bound rather than an exact figure: it is raised on
+    /// every insertion and recomputed when words are removed, but lowering a
+    /// word's rank in place (e.g. with `Autocompleter::remove_word_counted`)
+    /// can leave it higher than any rank actually stored below the node.
+    ///
+    /// # Return value
+    ///
+    /// Copy of the `max_subtree_rank` field of the given `MwtNode`.
+    pub fn get_max_subtree_rank(&self) -> i32 {
+        self.max_subtree_rank
+    }
+
+    /// Accessor method for the number of words stored at or below a `MwtNode`.
+    ///
+    /// # Return value
+    ///
+    /// Copy of the `subtree_word_count` field of the given `MwtNode`.
+    pub fn get_subtree_word_count(&self) -> usize {
+        self.subtree_word_count
+    }
+
+    /// Accessor method for the number of distinct lines a finished word was
+    /// read from, when document frequencies are tracked.
+    ///
+    /// # Return value
+    ///
+    /// Copy of the `doc_freq` field of the given `MwtNode`.
+    pub fn get_doc_freq(&self) -> u32 {
+        self.doc_freq
+    }
+
+    /// Accessor method for the sources recorded for a finished word, through
+    /// `Autocompleter::add_word_with_source`.
+    ///
+    /// # Return value
+    ///
+    /// The source identifiers in ascending order, or nothing if none were recorded.
+    pub fn get_sources(&self) -> impl Iterator<Item = u64> + '_ {
+        self.sources.iter().flat_map(|s| s.iter().copied())
+    }
+
+    /// Accessor method for the payload attached to a finished word, through
+    /// `Autocompleter::add_word_with_payload`.
+    ///
+    /// # Return value
+    ///
+    /// The payload, or `None` if the word was never given one.
+    pub fn get_payload(&self) -> Option<&T> {
+        self.payload.as_ref()
+    }
+
+    /// Accessor method for the spelling a finished word is shown with, when
+    /// `ignore_punctuation` or `preserve_case` stored it under a folded form.
+    /// That is the spelling it was added with most often, or the first one
+    /// seen of those tied for most.
+    ///
+    /// # Return value
+    ///
+    /// The spelling to show, or `None` if the word is shown as stored.
+    pub fn get_surface(&self) -> Option<&str> {
+        self.surfaces
+            .as_ref()
+            .and_then(|forms| forms.first())
+            .map(|(form, _)| &**form)
+    }
+
+    /// Iterates over every spelling a finished word was added with, for
+    /// callers that need more than `get_surface`.
+    ///
+    /// # Return value
+    ///
+    /// `(spelling, uses)` pairs, most used first. Empty if the word is shown
+    /// as stored.
+    pub(super) fn surfaces_iter(&self) -> impl Iterator<Item = (&str, i32)> {
+        self.surfaces
+            .iter()
+            .flat_map(|forms| forms.iter())
+            .map(|(form, uses)| (&**form, *uses))
+    }
+
     /// Accessor method for the end marker of a `MwtNode`.
     ///
     /// # Return value
@@ -61,18 +225,190 @@ impl MwtNode {
 
     /// Accessor method for the `children` map of a `MwtNode`.
     ///
+    /// Kept private so the storage type doesn't leak; use `children_iter` instead.
+    ///
     /// # Return value
     ///
     /// Reference of the `children` field of the given `MwtNode`.
-    pub fn get_children(&self) -> &HeapMap {
+    fn get_children(&self) -> &HeapMap<T> {
         &self.children
     }
 
+    /// Iterates over the children of a `MwtNode`.
+    ///
+    /// # Return value
+    ///
+    /// An iterator of `(character, child)` pairs in unspecified order.
+    pub fn children_iter(&self) -> impl Iterator<Item = (char, &MwtNode<T>)> {
+        self.children
+            .iter()
+            .filter_map(|(ch, nd)| nd.as_deref().map(|child| (*ch, child)))
+    }
+
+    /// Looks up the child a character leads to.
+    ///
+    /// # Arguments
+    ///
+    /// * `ch` (`char`) - Next character of a word.
+    ///
+    /// # Return value
+    ///
+    /// The child node, or `None` if no stored word continues with `ch`.
+    pub fn get_child(&self, ch: char) -> Option<&MwtNode<T>> {
+        self.children.get(&ch)?.as_deref()
+    }
+
+    /// Iterates over the children of a `MwtNode` mutably. Only reachable
+    /// through `Autocompleter::get_root_mut`.
+    ///
+    /// # Return value
+    ///
+    /// An iterator of `(character, child)` pairs in unspecified order.
+    pub fn children_iter_mut(&mut self) -> impl Iterator<Item = (char, &mut MwtNode<T>)> {
+        self.children
+            .iter_mut()
+            .filter_map(|(ch, nd)| nd.as_deref_mut().map(|child| (*ch, child)))
+    }
+
+    /// Looks up the child a character leads to, mutably. Only reachable
+    /// through `Autocompleter::get_root_mut`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ch` (`char`) - Next character of a word.
+    ///
+    /// # Return value
+    ///
+    /// The child node, or `None` if no stored word continues with `ch`.
+    pub fn get_child_mut(&mut self, ch: char) -> Option<&mut MwtNode<T>> {
+        self.children.get_mut(&ch)?.as_deref_mut()
+    }
+
+    /// Sets the rank of the word held at this node. Only reachable through
+    /// `Autocompleter::get_root_mut`, which refreshes the cached rank bounds
+    /// afterwards.
+    ///
+    /// Nodes that don't hold a word are left alone, and ranks are kept at 1
+    /// or more, since a rank of 0 would mean the word isn't stored. To remove
+    /// words, use `Autocompleter::retain` instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `rank` (`i32`) - New rank.
+    pub fn set_rank(&mut self, rank: i32) {
+        if self.get_end() {
+            self.rank = rank.max(1);
+        }
+    }
+
+    /// Accessor method for the number of children of a `MwtNode`.
+    ///
+    /// # Return value
+    ///
+    /// Number of distinct characters that continue from this node.
+    pub fn child_count(&self) -> usize {
+        self.children.len()
+    }
+
+    /// Accessor method for the clock value of the last insertion of this word.
+    ///
+    /// # Return value
+    ///
+    /// Copy of the `last_seen` field of the given `MwtNode`.
+    pub fn get_last_seen(&self) -> u64 {
+        self.last_seen
+    }
+
+    /// Accessor method for the time-decayed insertion count of this word.
+    ///
+    /// The value is as of the `last_seen` clock tick, so it still needs to be
+    /// decayed by however many insertions have happened since.
+    ///
+    /// # Return value
+    ///
+    /// Copy of the `recency` field of the given `MwtNode`.
+    pub fn get_recency(&self) -> f64 {
+        self.recency
+    }
+
+    /// Accessor method for the float score of this word.
+    ///
+    /// # Return value
+    ///
+    /// Copy of the `score` field of the given `MwtNode`, 0 for words never
+    /// given a score.
+    pub fn get_score(&self) -> f64 {
+        self.score
+    }
+
+    /// Mutator method for the `last_seen` clock value of a finished word.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` (`u64`) - Current value of the insertion clock.
+    fn set_last_seen(&mut self, now: u64) {
+        self.last_seen = now;
+    }
+
+    /// Mutator method for the `recency` of a finished word. Decays the stored
+    /// value by the insertions since `last_seen`, counts one new insertion,
+    /// and moves `last_seen` up to `now`.
+    ///
+    /// # Arguments
+    ///
+    /// * `decay` (`f64`) - Factor the score is multiplied by for every insertion.
+    /// * `now` (`u64`) - Current value of the insertion clock.
+    fn bump_recency(&mut self, decay: f64, now: u64) {
+        let elapsed = now - self.last_seen;
+        self.recency = self.recency * decay.powf(elapsed as f64) + 1.0;
+        self.last_seen = now;
+    }
+
     /// Mutator method for the `rank` of a finished word.
     /// Simply increments the field by one. Used whenever
     /// a word is inserted/re-inserted.
     fn increment_rank(&mut self) {
-        self.rank += 1;
+        self.increment_rank_by(1);
+    }
+
+    /// Mutator method for the `rank` of a finished word.
+    /// Increases the field by `n`, saturating at `i32::MAX` rather than
+    /// overflowing for words seen billions of times. Every rank increase goes
+    /// through here.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` (`i32`) - Amount to increase the rank by.
+    fn increment_rank_by(&mut self, n: i32) {
+        self.rank = self.rank.saturating_add(n);
+    }
+
+    /// Raises the `max_subtree_rank` bound to at least `rank`.
+    ///
+    /// # Arguments
+    ///
+    /// * `rank` (`i32`) - Rank of a word at or below this node.
+    fn raise_max_rank(&mut self, rank: i32) {
+        self.max_subtree_rank = self.max_subtree_rank.max(rank);
+    }
+
+    /// Recomputes the `max_subtree_rank` bound from the node's own rank and
+    /// its children's bounds, after a word below it has been removed.
+    fn recompute_max_rank(&mut self) {
+        self.max_subtree_rank = self
+            .children_iter()
+            .map(|(_, child)| child.max_subtree_rank)
+            .fold(self.rank, i32::max);
+    }
+
+    /// Recomputes the `subtree_word_count` from the node's own word and its
+    /// children's counts, after any number of words below it were removed.
+    fn recompute_word_count(&mut self) {
+        self.subtree_word_count = self
+            .children_iter()
+            .map(|(_, child)| child.subtree_word_count)
+            .sum::<usize>()
+            + usize::from(self.get_end());
     }
 
     /// Mutator method for the `data` field of a `MwtNode`.
@@ -80,16 +416,144 @@ impl MwtNode {
     /// # Arguments
     ///
     /// * `data` (`String`) - New value to set. Consumed by the function.
+    #[cfg(feature = "store-words")]
     fn set_data(&mut self, data: String) {
         self.data = data;
     }
 
+    /// Moves the node's own copy of its word out, leaving "" behind.
+    ///
+    /// # Return value
+    ///
+    /// The old `data` field, or "" if words aren't stored or the
+    /// `store-words` feature is off.
+    fn take_data(&mut self) -> String {
+        #[cfg(feature = "store-words")]
+        return std::mem::take(&mut self.data);
+        #[cfg(not(feature = "store-words"))]
+        return String::new();
+    }
+
     /// Mutator method for the `is_end` field of a `MwtNode`.
     ///
     /// Used when a word is updated to mark the node as containing a finished word.
     fn toggle_end(&mut self) {
         self.is_end = !self.is_end;
     }
+
+    /// Counts uses of one of the spellings the word at this node was added
+    /// with, for `Mwt::record_surface`, keeping the most used spelling first.
+    ///
+    /// # Arguments
+    ///
+    /// * `surface` (`&str`) - Spelling the word was added with
+    /// * `count` (`i32`) - Number of uses to count
+    fn add_surface(&mut self, surface: &str, count: i32) {
+        let forms = self.surfaces.get_or_insert_with(Box::default);
+        let mut i = match forms.iter().position(|(form, _)| **form == *surface) {
+            Some(i) => {
+                forms[i].1 = forms[i].1.saturating_add(count);
+                i
+            }
+            None => {
+                forms.push((surface.into(), count));
+                forms.len() - 1
+            }
+        };
+        while i > 0 && forms[i].1 > forms[i - 1].1 {
+            forms.swap(i, i - 1);
+            i -= 1;
+        }
+    }
+
+    /// Stops a node from holding a word, resetting everything stored about it.
+    ///
+    /// # Return value
+    ///
+    /// The word's `queued_at` value, so it can be dropped from the eviction order.
+    fn unmark(&mut self) -> u64 {
+        self.toggle_end();
+        self.take_data();
+        self.rank = 0;
+        self.recency = 0.0;
+        self.doc_freq = 0;
+        self.sources = None;
+        self.score = 0.0;
+        self.payload = None;
+        self.surfaces = None;
+        self.queued_at
+    }
+
+    /// Returns `true` if the node neither holds a word nor leads to one, so
+    /// its parent can drop it.
+    fn is_dangling(&self) -> bool {
+        !self.get_end() && self.children.is_empty()
+    }
+
+    /// Copies the node itself, leaving its children out.
+    ///
+    /// # Return value
+    ///
+    /// A new node holding the same word data and an empty children map.
+    fn clone_shallow(&self) -> MwtNode<T>
+    where
+        T: Clone,
+    {
+        MwtNode {
+            is_end: self.is_end,
+            #[cfg(feature = "store-words")]
+            data: self.data.clone(),
+            rank: self.rank,
+            children: Box::new(HashMap::with_capacity(self.children.len())),
+            last_seen: self.last_seen,
+            recency: self.recency,
+            last_used: AtomicU64::new(self.last_used.load(Ordering::Relaxed)),
+            queued_at: self.queued_at,
+            max_subtree_rank: self.max_subtree_rank,
+            subtree_word_count: self.subtree_word_count,
+            doc_freq: self.doc_freq,
+            sources: self.sources.clone(),
+            score: self.score,
+            payload: self.payload.clone(),
+            surfaces: self.surfaces.clone(),
+        }
+    }
+}
+
+/// Copies the subtree below a node iteratively, for the same reason as `Drop`.
+impl<T: Clone> Clone for MwtNode<T> {
+    fn clone(&self) -> MwtNode<T> {
+        // Copy every node on its own, parents before their children, then hand
+        // the copies to their parents from the back so each is complete first
+        let mut copies: Vec<(usize, char, Box<MwtNode<T>>)> = Vec::new();
+        let mut stack: Vec<(usize, char, &MwtNode<T>)> = vec![(0, '\0', self)];
+        while let Some((parent, ch, nd)) = stack.pop() {
+            let id = copies.len();
+            copies.push((parent, ch, Box::new(nd.clone_shallow())));
+            stack.extend(nd.children_iter().map(|(ch, child)| (id, ch, child)));
+        }
+        while let Some((parent, ch, nd)) = copies.pop() {
+            if copies.is_empty() {
+                return *nd;
+            }
+            copies[parent].2.children.insert(ch, Some(nd));
+        }
+        panic!("Unreachable code hit: copied node went missing!")
+    }
+}
+
+/// Dismantles the subtree below a node iteratively. The default drop would
+/// recurse once per level, so a single very long word could overflow the stack
+/// when the MWT is dropped.
+impl<T> Drop for MwtNode<T> {
+    fn drop(&mut self) {
+        let mut stack: Vec<Box<MwtNode<T>>> =
+            self.children.drain().filter_map(|(_, nd)| nd).collect();
+        while let Some(mut nd) = stack.pop() {
+            stack.extend(nd.children.drain().filter_map(|(_, child)| child));
+            // `nd` is dropped here with no children left, so this doesn't recurse
+        }
+    }
 }
 
 /// Implementation of the `MWT` itself.
@@ -100,57 +564,1535 @@ impl MwtNode {
 /// # Fields
 ///
 /// `root` (`Box<MwtNode>`) - Base node of the structure.
-pub struct Mwt {
-    root: Box<MwtNode>,
+///
+/// `clock` (`u64`) - Logical clock counting every insertion, used to stamp nodes.
+///
+/// `word_count` (`usize`) - Number of distinct words stored.
+///
+/// `max_words` (`Option<usize>`) - Cap on `word_count`, if any. What happens once it is
+/// reached is decided by `eviction`.
+///
+/// `eviction` (`EvictionPolicy`) - How a new word is handled when the MWT is full.
+///
+/// `max_word_len` (`Option<usize>`) - Longest word, in characters, that will be inserted.
+///
+/// `use_clock` (`AtomicU64`) - Logical clock counting every insertion and every word returned
+/// by a prediction, used to stamp `last_used`. Only advanced under
+/// `EvictionPolicy::LeastRecentlyUsed`.
+///
+/// `add_order` (`BTreeMap<u64, String>`) - Every stored word keyed by its `queued_at` use clock
+/// value, oldest first. Only maintained under `EvictionPolicy::LeastRecentlyUsed`.
+///
+/// `store_words` (`bool`) - Whether word nodes keep a copy of their word in `data`. Always
+/// `false` without the `store-words` feature.
+pub struct Mwt<T = ()> {
+    root: Box<MwtNode<T>>,
+    clock: u64,
+    word_count: usize,
+    max_words: Option<usize>,
+    eviction: EvictionPolicy,
+    use_clock: AtomicU64,
+    add_order: BTreeMap<u64, String>,
+    max_word_len: Option<usize>,
+    store_words: bool,
 }
 
 impl Mwt {
+    /// Works out how many nodes an MWT holding the given words would have,
+    /// without building it, by counting the distinct prefixes of the words.
+    ///
+    /// The count is exact and includes the root, so it matches `node_count`
+    /// after inserting the same words. It still costs memory proportional to
+    /// the total length of the words, for the set of prefixes, but far less
+    /// than the nodes themselves.
+    ///
+    /// # Arguments
+    ///
+    /// `words` (`I: IntoIterator<Item = &str>`) - Words that would be inserted.
+    ///
+    /// # Return value
+    ///
+    /// Number of nodes the MWT would have.
+    pub fn estimate_nodes<'a, I: IntoIterator<Item = &'a str>>(words: I) -> usize {
+        let mut prefixes: HashSet<&str> = HashSet::new();
+        for word in words {
+            for (i, ch) in word.char_indices() {
+                prefixes.insert(&word[..i + ch.len_utf8()]);
+            }
+        }
+        prefixes.len() + 1
+    }
+}
+
+impl<T> Mwt<T> {
     /// Constructs a new, empty `MWT`.
-    pub fn new() -> Mwt {
+    pub fn new() -> Mwt<T> {
         Mwt {
             root: Box::new(MwtNode::new()),
+            clock: 0,
+            word_count: 0,
+            max_words: None,
+            eviction: EvictionPolicy::default(),
+            use_clock: AtomicU64::new(0),
+            add_order: BTreeMap::new(),
+            max_word_len: None,
+            store_words: cfg!(feature = "store-words"),
         }
     }
 
-    /// Accessor method for the `root`.
+    /// Removes every word from the MWT, keeping its `max_words` cap, eviction
+    /// policy, `max_word_len` limit and whether words are stored.
+    pub fn clear(&mut self) {
+        *self = Mwt {
+            max_words: self.max_words,
+            eviction: self.eviction,
+            max_word_len: self.max_word_len,
+            store_words: self.store_words,
+            ..Mwt::new()
+        };
+    }
+
+    /// Reserves room in the root's children map ahead of a bulk load.
     ///
-    /// # Return value
+    /// Only the root can be sized up front: the maps further down depend on
+    /// which words arrive. The root can't have more children than there are
+    /// distinct first characters, so the reservation is capped at
+    /// `MAX_ROOT_RESERVE`.
     ///
-    /// Returns the reference to the `root` field.
-    pub fn get_root(&self) -> &Box<MwtNode> {
-        &self.root
+    /// # Arguments
+    ///
+    /// * `additional` (`usize`) - Number of words about to be inserted
+    pub fn reserve(&mut self, additional: usize) {
+        let wanted = additional.min(MAX_ROOT_RESERVE);
+        let len = self.root.children.len();
+        self.root.children.reserve(wanted.saturating_sub(len));
     }
 
-    /// Adds a new string to the MWT.
+    /// Shrinks every node's children map and word to fit what they hold, and
+    /// tightens every `max_subtree_rank` bound back to the exact maximum.
+    pub fn compact(&mut self) {
+        Mwt::post_order_mut(&mut self.root, |nd, _| {
+            nd.children.shrink_to_fit();
+            #[cfg(feature = "store-words")]
+            nd.data.shrink_to_fit();
+            nd.recompute_max_rank();
+            true
+        });
+    }
+
+    /// Tightens every `max_subtree_rank` bound back to the exact maximum,
+    /// after ranks have been changed in place.
+    pub fn recompute_bounds(&mut self) {
+        Mwt::post_order_mut(&mut self.root, |nd, _| {
+            nd.recompute_max_rank();
+            true
+        });
+    }
+
+    /// Calls `finish` on every node of the MWT, each only once everything
+    /// below it has been finished, so bounds can be rebuilt from exact child
+    /// values. Nodes for which `finish` returns `false` are dropped from their
+    /// parent; the root is always kept.
     ///
-    /// Iterates through the string to insert, creating
-    /// new `MwtNode`s as needed until the entire string is traversed,
-    /// then inserts the word at that node.
+    /// The walk is iterative, so very deep tries can't overflow the stack.
+    /// Children are moved out of their parent while the walk is below them and
+    /// put back once finished.
     ///
     /// # Arguments
     ///
-    /// * `data` (`String`) - New word to insert
-    pub fn add_record(&mut self, data: String) {
-        let mut tmp = &mut self.root;
+    /// * `root` (`&mut Box<MwtNode>`) - Root of the MWT
+    /// * `finish` (`F: FnMut(&mut MwtNode, &str) -> bool`) - Called with each node and its
+    ///   path from the root, returning whether to keep it
+    fn post_order_mut<F: FnMut(&mut MwtNode<T>, &str) -> bool>(
+        root: &mut Box<MwtNode<T>>,
+        mut finish: F,
+    ) {
+        // Each frame holds a node taken out of the MWT, the character leading
+        // to it and the children it still has to finish
+        type Frame<T> = (char, Box<MwtNode<T>>, Vec<(char, Box<MwtNode<T>>)>);
+        let pending = |nd: &mut MwtNode<T>| -> Vec<(char, Box<MwtNode<T>>)> {
+            nd.children
+                .drain()
+                .filter_map(|(ch, child)| child.map(|c| (ch, c)))
+                .collect()
+        };
 
-        // Traverse MWT character by character
-        for ch in data.chars() {
-            let children = &mut tmp.children;
-            // If the value isn't present, add it to the map
-            if !children.contains_key(&ch) {
-                children.insert(ch, Some(Box::new(MwtNode::new())));
+        let mut top = std::mem::replace(root, Box::new(MwtNode::new()));
+        let children = pending(&mut top);
+        let mut frames: Vec<Frame<T>> = vec![('\0', top, children)];
+        let mut path = String::new();
+        loop {
+            let Some((_, _, children)) = frames.last_mut() else {
+                panic!("Unreachable code hit: walk lost the root!");
+            };
+            if let Some((ch, mut nd)) = children.pop() {
+                let children = pending(&mut nd);
+                path.push(ch);
+                frames.push((ch, nd, children));
+                continue;
             }
-            tmp = match children.get_mut(&ch).unwrap() {
-                Some(nd) => nd,
-                None => panic!("Unreachable code hit: existing child had non-existing node!"),
+
+            let Some((ch, mut nd, _)) = frames.pop() else {
+                panic!("Unreachable code hit: walk lost the root!");
+            };
+            let keep = finish(&mut nd, &path);
+            match frames.last_mut() {
+                Some((_, parent, _)) => {
+                    path.pop();
+                    if keep {
+                        parent.children.insert(ch, Some(nd));
+                    }
+                }
+                None => {
+                    *root = nd;
+                    return;
+                }
             }
         }
+    }
 
-        // Insert the new word at the end
-        if !tmp.get_end() {
-            tmp.toggle_end();
-            tmp.set_data(data);
+    /// Walks the whole MWT and checks that its structure is consistent, as a
+    /// safety net for MWTs that weren't built through the usual insertions.
+    ///
+    /// The invariants checked are:
+    ///
+    /// * every child entry holds a node
+    /// * every word node has a rank above 0, and a non-empty `data` equal to its
+    ///   path from the root, or an empty one if words aren't stored
+    /// * every other node has empty `data` and, apart from the root, at least one child
+    /// * every `subtree_word_count` matches the words actually below it
+    /// * every `max_subtree_rank` is at least the highest rank below it
+    /// * the stored word count matches the number of word nodes
+    /// * under `LeastRecentlyUsed` eviction, the eviction order holds every word exactly once,
+    ///   filed no later than its last use
+    ///
+    /// # Return value
+    ///
+    /// `Ok(())` if every invariant holds, or an error describing the first
+    /// violation found.
+    pub fn validate(&self) -> Result<(), String> {
+        let words = self.validate_nodes()?;
+        if self.root.get_end() {
+            return Err("Invariant violated: the root holds a word".to_string());
         }
-        tmp.increment_rank(); // Increase number of times we've seen this word
+        if words != self.word_count {
+            return Err(format!(
+                "Invariant violated: word count is {} but {words} words are stored",
+                self.word_count
+            ));
+        }
+        if self.eviction == EvictionPolicy::LeastRecentlyUsed {
+            if self.add_order.len() != words {
+                return Err(format!(
+                    "Invariant violated: eviction order holds {} words but {words} are stored",
+                    self.add_order.len()
+                ));
+            }
+            for (queued_at, word) in &self.add_order {
+                match self.find_node(word) {
+                    Some(nd)
+                        if nd.get_end()
+                            && nd.queued_at == *queued_at
+                            && nd.last_used.load(Ordering::Relaxed) >= *queued_at => {}
+                    _ => {
+                        return Err(format!(
+                            "Invariant violated: eviction order entry `{word}` doesn't match a stored word"
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Helper for `validate` that checks every node and the cached counts and
+    /// bounds. The walk is iterative, so very deep tries can't overflow the stack.
+    ///
+    /// # Return value
+    ///
+    /// The number of words stored, or an error describing the first
+    /// violation found.
+    fn validate_nodes(&self) -> Result<usize, String> {
+        // Every node with the index of its parent and the character leading to
+        // it, parents before their children
+        let mut nodes: Vec<(usize, char, &MwtNode<T>)> = Vec::new();
+        let path_of = |nodes: &[(usize, char, &MwtNode<T>)], mut id: usize| -> String {
+            let mut chars = Vec::new();
+            while id > 0 {
+                chars.push(nodes[id].1);
+                id = nodes[id].0;
+            }
+            chars.into_iter().rev().collect()
+        };
+
+        let mut path = String::new();
+        let mut stack: Vec<(usize, usize, Option<char>, &MwtNode<T>)> =
+            vec![(0, 0, None, &self.root)];
+        while let Some((parent, len, ch, node)) = stack.pop() {
+            path.truncate(len);
+            path.extend(ch);
+            if node.get_end() {
+                let expected = if self.store_words { path.as_str() } else { "" };
+                if path.is_empty() || node.stored_word() != expected {
+                    return Err(format!(
+                        "Invariant violated at `{path}`: word node holds `{}` instead of its path",
+                        node.stored_word()
+                    ));
+                }
+                if node.rank <= 0 {
+                    return Err(format!(
+                        "Invariant violated at `{path}`: word has rank {}",
+                        node.rank
+                    ));
+                }
+            } else {
+                if !node.stored_word().is_empty() {
+                    return Err(format!(
+                        "Invariant violated at `{path}`: non-word node holds `{}`",
+                        node.stored_word()
+                    ));
+                }
+                if !path.is_empty() && node.children.is_empty() {
+                    return Err(format!(
+                        "Invariant violated at `{path}`: branch leads to no word"
+                    ));
+                }
+            }
+
+            let id = nodes.len();
+            nodes.push((parent, ch.unwrap_or_default(), node));
+            for (ch, child) in node.children.iter() {
+                let child = child.as_deref().ok_or_else(|| {
+                    format!("Invariant violated at `{path}{ch}`: child entry holds no node")
+                })?;
+                stack.push((id, path.len(), Some(*ch), child));
+            }
+        }
+
+        // Children come after their parents, so walking backwards has every
+        // node's totals complete before they are added to its parent's
+        let mut totals: Vec<(usize, i32)> = nodes
+            .iter()
+            .map(|(_, _, nd)| match nd.get_end() {
+                true => (1, nd.rank),
+                false => (0, 0),
+            })
+            .collect();
+        for id in (0..nodes.len()).rev() {
+            let node = nodes[id].2;
+            let (words, max_rank) = totals[id];
+            if node.subtree_word_count != words {
+                return Err(format!(
+                    "Invariant violated at `{}`: cached word count is {} but {words} words are below",
+                    path_of(&nodes, id),
+                    node.subtree_word_count
+                ));
+            }
+            if node.max_subtree_rank < max_rank {
+                return Err(format!(
+                    "Invariant violated at `{}`: cached rank bound {} is below rank {max_rank}",
+                    path_of(&nodes, id),
+                    node.max_subtree_rank
+                ));
+            }
+            if id > 0 {
+                let parent = &mut totals[nodes[id].0];
+                parent.0 += words;
+                parent.1 = parent.1.max(max_rank);
+            }
+        }
+        Ok(totals[0].0)
+    }
+
+    /// Accessor method for the number of distinct words stored.
+    ///
+    /// # Return value
+    ///
+    /// Copy of the `word_count` field.
+    pub fn len(&self) -> usize {
+        self.word_count
+    }
+
+    /// Mutator method for the cap on distinct words.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_words` (`Option<usize>`) - New cap, or `None` for no limit.
+    pub fn set_max_words(&mut self, max_words: Option<usize>) {
+        self.max_words = max_words;
+    }
+
+    /// Mutator method for the longest word that will be inserted.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_word_len` (`Option<usize>`) - New limit in characters, or `None` for no limit.
+    pub fn set_max_word_len(&mut self, max_word_len: Option<usize>) {
+        self.max_word_len = max_word_len;
+    }
+
+    /// Mutator method for whether word nodes keep a copy of their word.
+    ///
+    /// Must be called before any words are added, since words already stored
+    /// keep whatever copy they have. Without the `store-words` feature nodes
+    /// have no `data` field, so words are never stored whatever this is set to.
+    ///
+    /// # Arguments
+    ///
+    /// * `store_words` (`bool`) - `false` to leave `data` empty and rebuild words from paths.
+    pub fn set_store_words(&mut self, store_words: bool) {
+        self.store_words = store_words && cfg!(feature = "store-words");
+    }
+
+    /// Mutator method for the policy applied once `max_words` is reached.
+    ///
+    /// Must be called before any words are added, since the use order
+    /// needed for eviction is only tracked from then on.
+    ///
+    /// # Arguments
+    ///
+    /// * `eviction` (`EvictionPolicy`) - New policy.
+    pub fn set_eviction(&mut self, eviction: EvictionPolicy) {
+        self.eviction = eviction;
+    }
+
+    /// Counts a word as used for `EvictionPolicy::LeastRecentlyUsed`, so it
+    /// is evicted after every word used less recently. Only needs a shared
+    /// reference, so predictions can call it. Does nothing under other
+    /// policies, or if the word isn't stored.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` (`&str`) - Word that was used
+    pub fn mark_used(&self, data: &str) {
+        if self.eviction != EvictionPolicy::LeastRecentlyUsed {
+            return;
+        }
+        if let Some(nd) = self.find_node(data).filter(|nd| nd.get_end()) {
+            let now = self.use_clock.fetch_add(1, Ordering::Relaxed) + 1;
+            nd.last_used.fetch_max(now, Ordering::Relaxed);
+        }
+    }
+
+    /// Accessor method for the insertion `clock`.
+    ///
+    /// # Return value
+    ///
+    /// Number of insertions made into this MWT so far.
+    pub fn get_clock(&self) -> u64 {
+        self.clock
+    }
+
+    /// Accessor method for the `root`.
+    ///
+    /// # Return value
+    ///
+    /// Returns the reference to the `root` field.
+    pub fn get_root(&self) -> &MwtNode<T> {
+        &self.root
+    }
+
+    /// Counts every `MwtNode` in the structure, including the root.
+    ///
+    /// # Return value
+    ///
+    /// Total number of nodes.
+    pub fn node_count(&self) -> usize {
+        let mut count = 0;
+        let mut stack: Vec<&MwtNode<T>> = vec![self.get_root()];
+        while let Some(nd) = stack.pop() {
+            count += 1;
+            stack.extend(nd.children_iter().map(|(_, child)| child));
+        }
+        count
+    }
+
+    /// Gives a rough estimate of the heap memory used by the structure.
+    ///
+    /// Each node is counted as its own size, plus its boxed children map with
+    /// one entry and one control byte per slot of capacity, plus the length of
+    /// its stored word, if any. Allocator overhead and the hash map's group padding
+    /// are ignored, so treat the result as a lower bound.
+    ///
+    /// # Return value
+    ///
+    /// Estimated number of bytes.
+    pub fn estimated_memory_bytes(&self) -> usize {
+        let entry_size = size_of::<(char, Option<Box<MwtNode<T>>>)>() + 1;
+        let mut bytes = size_of::<Mwt<T>>();
+        let mut stack: Vec<&MwtNode<T>> = vec![self.get_root()];
+        while let Some(nd) = stack.pop() {
+            bytes += size_of::<MwtNode<T>>()
+                + size_of::<HashMap<char, Option<Box<MwtNode<T>>>>>()
+                + nd.children.capacity() * entry_size
+                + nd.stored_word().len()
+                + nd.surfaces.as_ref().map_or(0, |forms| {
+                    forms.capacity() * size_of::<(Box<str>, i32)>()
+                        + forms.iter().map(|(form, _)| form.len()).sum::<usize>()
+                });
+            stack.extend(nd.children_iter().map(|(_, child)| child));
+        }
+        bytes
+    }
+
+    /// Builds a new MWT holding a copy of every word under `prefix`, with the
+    /// same ranks and insertion stamps. Words keep their full spelling,
+    /// prefix included. The `max_words` cap and eviction policy carry over.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` (`&str`) - Prefix whose subtree to copy
+    ///
+    /// # Return value
+    ///
+    /// The new MWT, or `None` if no stored word begins with `prefix`.
+    pub fn subtree(&self, prefix: &str) -> Option<Mwt<T>>
+    where
+        T: Clone,
+    {
+        let node = self.find_node(prefix)?;
+        let mut sub = Mwt {
+            clock: self.clock,
+            max_words: self.max_words,
+            eviction: self.eviction,
+            use_clock: AtomicU64::new(self.use_clock.load(Ordering::Relaxed)),
+            max_word_len: self.max_word_len,
+            store_words: self.store_words,
+            ..Mwt::new()
+        };
+
+        // Recreate the path down to the prefix, then graft a copy of its node on
+        let mut tmp = &mut sub.root;
+        let mut chars = prefix.chars().peekable();
+        while let Some(ch) = chars.next() {
+            let child = if chars.peek().is_none() {
+                node.clone()
+            } else {
+                let mut path_node = MwtNode::new();
+                path_node.raise_max_rank(node.max_subtree_rank);
+                path_node.subtree_word_count = node.subtree_word_count;
+                path_node
+            };
+            tmp = match tmp.children.entry(ch).or_insert(Some(Box::new(child))) {
+                Some(nd) => nd,
+                None => panic!("Unreachable code hit: existing child had non-existing node!"),
+            };
+        }
+        if prefix.is_empty() {
+            sub.root = Box::new(node.clone());
+        }
+        sub.root.raise_max_rank(node.max_subtree_rank);
+        sub.root.subtree_word_count = node.subtree_word_count;
+
+        // Recount the copied words, and rebuild the eviction order if it's tracked
+        let mut word_count = 0;
+        let mut add_order = BTreeMap::new();
+        Mwt::for_each_word(&sub.root, "", false, |word, nd| {
+            word_count += 1;
+            if sub.eviction == EvictionPolicy::LeastRecentlyUsed {
+                add_order.insert(nd.queued_at, word.to_string());
+            }
+        });
+        sub.word_count = word_count;
+        sub.add_order = add_order;
+        Some(sub)
+    }
+
+    /// Renders the MWT as an indented text tree, one node per line, for
+    /// debugging. Each line shows the node's character, with a `*` and the
+    /// rank for nodes holding a word, e.g. `    t* (rank 3)`. Children are
+    /// listed in character order.
+    ///
+    /// The walk is iterative, so very deep tries can't overflow the stack.
+    ///
+    /// # Return value
+    ///
+    /// The rendered tree, starting with a `(root)` line.
+    pub fn dump_tree(&self) -> String {
+        let mut out = String::from("(root)\n");
+        let mut stack: Vec<(usize, char, &MwtNode<T>)> = Vec::new();
+        Mwt::push_sorted_children(&self.root, 1, &mut stack);
+        while let Some((depth, ch, nd)) = stack.pop() {
+            out.push_str(&"  ".repeat(depth));
+            out.push(ch);
+            if nd.get_end() {
+                out.push_str(&format!("* (rank {})", nd.get_rank()));
+            }
+            out.push('\n');
+            Mwt::push_sorted_children(nd, depth + 1, &mut stack);
+        }
+        out
+    }
+
+    /// Renders the MWT as a GraphViz DOT digraph, for visualizing small
+    /// dictionaries with e.g. `dot -Tpng`. Each edge is labeled with its
+    /// character, and nodes holding a word are drawn as double circles
+    /// labeled with their rank. Children are listed in character order.
+    ///
+    /// The walk is iterative, so very deep tries can't overflow the stack.
+    ///
+    /// # Return value
+    ///
+    /// The DOT source, with nodes named `n0` (the root), `n1` and so on.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph mwt {\n    node [shape=circle, label=\"\"];\n");
+        out.push_str("    n0 [shape=box, label=\"root\"];\n");
+        let mut next_id = 1;
+        let mut stack: Vec<(usize, char, &MwtNode<T>)> = Vec::new();
+        // `push_sorted_children` takes a depth, which doubles as the parent's id here
+        Mwt::push_sorted_children(&self.root, 0, &mut stack);
+        while let Some((parent, ch, nd)) = stack.pop() {
+            let id = next_id;
+            next_id += 1;
+            if nd.get_end() {
+                out.push_str(&format!(
+                    "    n{id} [shape=doublecircle, label=\"{}\"];\n",
+                    nd.get_rank()
+                ));
+            }
+            out.push_str(&format!(
+                "    n{parent} -> n{id} [label=\"{}\"];\n",
+                Self::escape_dot(ch)
+            ));
+            Mwt::push_sorted_children(nd, id, &mut stack);
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Helper for `to_dot`. Escapes a character for use inside a quoted DOT
+    /// label.
+    ///
+    /// # Arguments
+    ///
+    /// * `ch` (`char`) - Character to escape
+    ///
+    /// # Return value
+    ///
+    /// The character, with a backslash in front if it is a quote or a
+    /// backslash, and control characters such as newlines written as escapes.
+    fn escape_dot(ch: char) -> String {
+        match ch {
+            '"' | '\\' => format!("\\{ch}"),
+            '\n' => "\\n".to_string(),
+            c if c.is_control() => c.escape_default().to_string(),
+            c => c.to_string(),
+        }
+    }
+
+    /// Helper for `dump_tree`. Pushes the children of `node` onto the stack
+    /// so that they pop off in character order.
+    ///
+    /// # Arguments
+    ///
+    /// * `node` (`&MwtNode`) - Node whose children to push
+    /// * `depth` (`usize`) - Depth of the children, or for `to_dot` the id of `node`
+    /// * `stack` (`&mut Vec<(usize, char, &MwtNode)>`) - Stack of nodes still to render
+    fn push_sorted_children<'a>(
+        node: &'a MwtNode<T>,
+        depth: usize,
+        stack: &mut Vec<(usize, char, &'a MwtNode<T>)>,
+    ) {
+        let mut children: Vec<(char, &MwtNode<T>)> = node.children_iter().collect();
+        children.sort_unstable_by_key(|&(ch, _)| Reverse(ch));
+        stack.extend(children.into_iter().map(|(ch, child)| (depth, ch, child)));
+    }
+
+    /// Collects every stored word with its rank, sorted by word.
+    ///
+    /// # Return value
+    ///
+    /// A vector of `(word, rank)` pairs in alphabetical order.
+    fn sorted_records(&self) -> Vec<(String, i32)> {
+        let mut records = Vec::new();
+        Mwt::for_each_word(self.get_root(), "", true, |word, nd| {
+            records.push((word.to_string(), nd.get_rank()));
+        });
+        records
+    }
+
+    /// Calls `visit` with every word at or below `node`, together with the
+    /// node holding it. Words are rebuilt from the path as the walk goes, so
+    /// this works whether or not nodes store their words.
+    ///
+    /// The walk is iterative, so very deep tries can't overflow the stack.
+    ///
+    /// # Arguments
+    ///
+    /// * `node` (`&MwtNode`) - Node to start from
+    /// * `prefix` (`&str`) - Characters leading from the root to `node`
+    /// * `sorted` (`bool`) - Whether to visit the words in alphabetical order. Otherwise the
+    ///   order is unspecified
+    /// * `visit` (`F: FnMut(&str, &MwtNode)`) - Called with each word and its node
+    pub fn for_each_word<'a, F: FnMut(&str, &'a MwtNode<T>)>(
+        node: &'a MwtNode<T>,
+        prefix: &str,
+        sorted: bool,
+        mut visit: F,
+    ) {
+        Mwt::walk(node, prefix, sorted, |word, _, nd| {
+            if nd.get_end() {
+                visit(word, nd);
+            }
+            true
+        });
+    }
+
+    /// Calls `visit` with every node at or below `node` in depth-first
+    /// order, each before the nodes below it, together with its path from
+    /// the root and its depth below `node`. Returning `false` from `visit`
+    /// skips everything below that node.
+    ///
+    /// The walk is iterative, so very deep tries can't overflow the stack.
+    ///
+    /// # Arguments
+    ///
+    /// * `node` (`&MwtNode`) - Node to start from, at depth 0
+    /// * `prefix` (`&str`) - Characters leading from the root to `node`
+    /// * `sorted` (`bool`) - Whether to visit children in character order. Otherwise the
+    ///   order is unspecified
+    /// * `visit` (`F: FnMut(&str, usize, &MwtNode) -> bool`) - Called with each path, depth
+    ///   and node, returning whether to descend below it
+    pub fn walk<'a, F: FnMut(&str, usize, &'a MwtNode<T>) -> bool>(
+        node: &'a MwtNode<T>,
+        prefix: &str,
+        sorted: bool,
+        mut visit: F,
+    ) {
+        let mut path = prefix.to_string();
+        // Each entry holds the length of its parent's path, so siblings can
+        // truncate back to it before adding their own character
+        let mut stack: Vec<(usize, usize, Option<char>, &MwtNode<T>)> =
+            vec![(path.len(), 0, None, node)];
+        while let Some((len, depth, ch, nd)) = stack.pop() {
+            path.truncate(len);
+            path.extend(ch);
+            if !visit(&path, depth, nd) {
+                continue;
+            }
+
+            let len = path.len();
+            let below = |(ch, child)| (len, depth + 1, Some(ch), child);
+            if sorted {
+                let mut children: Vec<(char, &MwtNode<T>)> = nd.children_iter().collect();
+                children.sort_unstable_by_key(|&(ch, _)| Reverse(ch));
+                stack.extend(children.into_iter().map(below));
+            } else {
+                stack.extend(nd.children_iter().map(below));
+            }
+        }
+    }
+
+    /// Walks the MWT along the characters of `prefix`.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` (`&str`) - Characters to follow from the root
+    ///
+    /// # Return value
+    ///
+    /// The node reached after consuming all of `prefix`, or `None` if the walk
+    /// falls off the MWT first.
+    pub fn find_node(&self, prefix: &str) -> Option<&MwtNode<T>> {
+        let mut tmp = self.get_root();
+
+        for ch in prefix.chars() {
+            tmp = match tmp.get_children().get(&ch)? {
+                Some(nd) => nd,
+                None => panic!("Unreachable code hit: existing child had non-existing node!"),
+            }
+        }
+        Some(tmp)
+    }
+
+    /// Mutable counterpart of `find_node`.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` (`&str`) - String to find
+    ///
+    /// # Return value
+    ///
+    /// The node `prefix` leads to, or `None` if no stored word begins with it.
+    fn find_node_mut(&mut self, prefix: &str) -> Option<&mut MwtNode<T>> {
+        let mut tmp: &mut MwtNode<T> = &mut self.root;
+        for ch in prefix.chars() {
+            tmp = tmp.get_child_mut(ch)?;
+        }
+        Some(tmp)
+    }
+
+    /// Adds a new string to the MWT.
+    ///
+    /// The word's node is stamped with the current insertion clock value. An
+    /// empty string is ignored, so the root never becomes an end node. A new
+    /// word arriving once the `max_words` cap has been reached is handled
+    /// according to the eviction policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` (`String`) - New word to insert
+    ///
+    /// # Return value
+    ///
+    /// The word's rank after insertion, or 0 if it was ignored or rejected.
+    pub fn add_record(&mut self, data: String) -> i32 {
+        self.insert_record(data, None, |_| {})
+            .map_or(0, |nd| nd.rank)
+    }
+
+    /// Adds a new string to the MWT like `add_record`, and also updates the
+    /// word's time-decayed `recency` score.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` (`String`) - New word to insert
+    /// * `decay` (`f64`) - Factor scores are multiplied by for every insertion
+    ///
+    /// # Return value
+    ///
+    /// The word's rank after insertion, or 0 if it was ignored or rejected.
+    pub fn add_record_decayed(&mut self, data: String, decay: f64) -> i32 {
+        self.insert_record(data, Some(decay), |_| {})
+            .map_or(0, |nd| nd.rank)
+    }
+
+    /// Adds a string to the MWT as if it had been inserted `count` times.
+    ///
+    /// Used when loading pre-counted data. Non-positive counts are ignored, as
+    /// are the strings `add_record` ignores. The rank saturates at `i32::MAX`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` (`String`) - New word to insert
+    /// * `count` (`i32`) - Amount to increase the word's rank by
+    pub fn add_record_with_count(&mut self, data: String, count: i32) {
+        self.merge_record(data, count, 0);
+    }
+
+    /// Adds a string to the MWT like `add_record_with_count`, and also adds
+    /// `doc_freq` to its document frequency. Used to merge in a word from
+    /// another MWT.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` (`String`) - New word to insert
+    /// * `count` (`i32`) - Amount to increase the word's rank by
+    /// * `doc_freq` (`u32`) - Amount to increase the word's document frequency by
+    pub fn merge_record(&mut self, data: String, count: i32, doc_freq: u32) {
+        if count <= 0 {
+            return;
+        }
+        // `insert_record` already counts one insertion.
+        self.insert_record(data, None, |nd| {
+            nd.increment_rank_by(count - 1);
+            nd.doc_freq = nd.doc_freq.saturating_add(doc_freq);
+        });
+    }
+
+    /// Adds a string to the MWT like `add_record`, read from a line of a
+    /// document, counting the line towards the word's document frequency the
+    /// first time the word appears in it.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` (`String`) - New word to insert
+    /// * `decay` (`Option<f64>`) - If set, the node's `recency` is also updated with this factor
+    /// * `first_in_line` (`bool`) - Whether this is the word's first appearance on its line
+    pub fn add_record_in_line(&mut self, data: String, decay: Option<f64>, first_in_line: bool) {
+        self.insert_record(data, decay, |nd| {
+            if first_in_line {
+                nd.doc_freq = nd.doc_freq.saturating_add(1);
+            }
+        });
+    }
+
+    /// Adds a string to the MWT like `add_record`, and also records that it
+    /// was seen in the source identified by `source`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` (`String`) - New word to insert
+    /// * `decay` (`Option<f64>`) - If set, the node's `recency` is also updated with this factor
+    /// * `source` (`u64`) - Identifier of the source the word came from
+    pub fn add_record_with_source(&mut self, data: String, decay: Option<f64>, source: u64) {
+        self.insert_record(data, decay, |nd| {
+            nd.sources.get_or_insert_with(Box::default).insert(source);
+        });
+    }
+
+    /// Adds a string to the MWT like `add_record`, and also adds `score` to
+    /// its float score.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` (`String`) - New word to insert
+    /// * `score` (`f64`) - Amount to increase the word's score by
+    pub fn add_record_with_score(&mut self, data: String, score: f64) {
+        self.insert_record(data, None, |nd| nd.score += score);
+    }
+
+    /// Adds a string to the MWT like `add_record`, and attaches `payload` to
+    /// it, replacing any payload it had before.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` (`String`) - New word to insert
+    /// * `decay` (`Option<f64>`) - If set, the node's `recency` is also updated with this factor
+    /// * `payload` (`T`) - Data to attach to the word
+    pub fn add_record_with_payload(&mut self, data: String, decay: Option<f64>, payload: T) {
+        self.insert_record(data, decay, |nd| nd.payload = Some(payload));
+    }
+
+    /// Counts a use of one of the spellings a stored word was added with, so
+    /// the most used one can be shown in place of the stored form. Nothing
+    /// happens if `data` isn't stored, e.g. because it was rejected.
+    ///
+    /// The spellings are kept in order of use, with ties left in the order
+    /// they were first seen, so the first one is always the one to show.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` (`&str`) - Word as stored
+    /// * `surface` (`String`) - Spelling it was added with
+    /// * `count` (`i32`) - Number of uses to count
+    pub fn record_surface(&mut self, data: &str, surface: String, count: i32) {
+        if let Some(nd) = self.find_node_mut(data).filter(|nd| nd.get_end()) {
+            nd.add_surface(&surface, count);
+        }
+    }
+
+    /// Merges in a word from another MWT, e.g. one staged by
+    /// `Autocompleter::merge_from_file`, like `merge_record`, but brings along
+    /// everything else recorded about it: its float score, sources and
+    /// spellings. The payload isn't copied.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` (`String`) - Word to insert, as stored in both MWTs
+    /// * `from` (`&MwtNode`) - Node holding the word in the other MWT
+    pub fn merge_node(&mut self, data: String, from: &MwtNode<T>) {
+        let count = from.rank;
+        if count <= 0 {
+            return;
+        }
+        self.insert_record(data, None, |nd| {
+            // `insert_record` already counts one insertion.
+            nd.increment_rank_by(count - 1);
+            nd.doc_freq = nd.doc_freq.saturating_add(from.doc_freq);
+            nd.score += from.score;
+            if let Some(sources) = &from.sources {
+                nd.sources
+                    .get_or_insert_with(Box::default)
+                    .extend(sources.iter());
+            }
+            for (form, uses) in from.surfaces_iter() {
+                nd.add_surface(form, uses);
+            }
+        });
+    }
+
+    /// Moves a stored word to a new spelling, keeping what was recorded about
+    /// it. The old word is removed first, pruning its branch, and then the
+    /// new one is inserted with the old rank, document frequency, score and
+    /// sources added to whatever it already had. The old payload, if any,
+    /// replaces the new word's. The old spelling is dropped along with the
+    /// old word.
+    ///
+    /// # Arguments
+    ///
+    /// * `old` (`&str`) - Word to move
+    /// * `new` (`String`) - Spelling to move it to
+    ///
+    /// # Return value
+    ///
+    /// `true` if `old` was stored and has been moved. Nothing changes if it
+    /// wasn't, or if `new` is empty or too long to be stored.
+    pub fn rename_record(&mut self, old: &str, new: String) -> bool {
+        let nd = match self.find_node(old) {
+            Some(nd) if nd.get_end() => nd,
+            _ => return false,
+        };
+        if !self.accepts(&new) {
+            return false;
+        }
+        if old == new {
+            return true;
+        }
+        let (rank, doc_freq, score, sources) = (nd.rank, nd.doc_freq, nd.score, nd.sources.clone());
+        let payload = self.find_node_mut(old).and_then(|nd| nd.payload.take());
+
+        // Removing first frees up room, so the insertion can't be rejected by
+        // the `max_words` cap
+        self.remove_record(old);
+        self.insert_record(new, None, |nd| {
+            // `insert_record` already counts one insertion.
+            nd.increment_rank_by(rank - 1);
+            nd.doc_freq = nd.doc_freq.saturating_add(doc_freq);
+            nd.score += score;
+            if let Some(sources) = sources {
+                nd.sources
+                    .get_or_insert_with(Box::default)
+                    .extend(sources.iter());
+            }
+            if payload.is_some() {
+                nd.payload = payload;
+            }
+        });
+        true
+    }
+
+    /// Sets a word's rank to an exact value, inserting the word first if it
+    /// isn't stored. A rank of 0 or less removes the word instead.
+    ///
+    /// Setting the rank counts as an insertion for the clock and the eviction
+    /// policy, so a new word can still be rejected when the MWT is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` (`String`) - Word to update
+    /// * `rank` (`i32`) - New rank
+    ///
+    /// # Return value
+    ///
+    /// `true` if the word was already stored beforehand.
+    pub fn set_record_rank(&mut self, data: String, rank: i32) -> bool {
+        if rank <= 0 {
+            return self.remove_record(&data);
+        }
+        let existed = self.find_node(&data).is_some_and(|nd| nd.get_end());
+        self.insert_record(data, None, |nd| nd.rank = rank);
+        existed
+    }
+
+    /// Removes a word from the MWT entirely, whatever its rank.
+    ///
+    /// Any nodes left without a word or children are pruned so no dangling
+    /// branches remain.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` (`&str`) - Word to remove
+    ///
+    /// # Return value
+    ///
+    /// `true` if the word was stored and has been removed.
+    pub fn remove_record(&mut self, data: &str) -> bool {
+        let Some(nd) = self.find_node_mut(data).filter(|nd| nd.get_end()) else {
+            return false;
+        };
+        let queued_at = nd.unmark();
+        let chars: Vec<char> = data.chars().collect();
+        self.prune_dangling(&chars);
+        self.fix_path(&chars, 1);
+        self.word_count -= 1;
+        self.add_order.remove(&queued_at);
+        true
+    }
+
+    /// Lowers a word's rank by one, removing it entirely once the rank
+    /// reaches zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` (`&str`) - Word to decrement
+    ///
+    /// # Return value
+    ///
+    /// The word's rank afterwards, which is 0 if it was removed or wasn't stored.
+    pub fn decrement_record(&mut self, data: &str) -> i32 {
+        let mut tmp: &mut MwtNode<T> = &mut self.root;
+        for ch in data.chars() {
+            tmp = match tmp.children.get_mut(&ch).and_then(|c| c.as_deref_mut()) {
+                Some(nd) => nd,
+                None => return 0,
+            };
+        }
+        if !tmp.get_end() {
+            return 0;
+        }
+        if tmp.rank > 1 {
+            tmp.rank -= 1;
+            return tmp.rank;
+        }
+        self.remove_record(data);
+        0
+    }
+
+    /// Removes every stored word for which `pred` returns `false`, pruning
+    /// branches that no longer lead to a word.
+    ///
+    /// # Arguments
+    ///
+    /// * `pred` (`F: FnMut(&str, i32) -> bool`) - Called with each word and its rank
+    ///
+    /// # Return value
+    ///
+    /// Number of words removed.
+    pub fn retain<F: FnMut(&str, i32) -> bool>(&mut self, mut pred: F) -> usize {
+        let mut removed: Vec<u64> = Vec::new();
+        Mwt::post_order_mut(&mut self.root, |nd, path| {
+            if nd.get_end() && !pred(path, nd.get_rank()) {
+                removed.push(nd.unmark());
+            }
+            nd.recompute_max_rank();
+            nd.recompute_word_count();
+            !nd.is_dangling()
+        });
+        self.word_count -= removed.len();
+        for queued_at in &removed {
+            self.add_order.remove(queued_at);
+        }
+        removed.len()
+    }
+
+    /// Removes every word starting with `prefix`, including `prefix` itself
+    /// if it is a word, by detaching the whole branch below it. Only the path
+    /// to the prefix and the removed branch are walked, however big the rest
+    /// of the MWT is.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` (`&str`) - Beginning shared by the words to remove
+    ///
+    /// # Return value
+    ///
+    /// Number of words removed.
+    pub fn remove_prefix(&mut self, prefix: &str) -> usize {
+        let chars: Vec<char> = prefix.chars().collect();
+        let (branch, path) = match chars.split_last() {
+            // The root itself is never detached, so its children are handed
+            // over to a fresh node instead
+            None => {
+                let mut branch = Box::new(MwtNode::new());
+                std::mem::swap(&mut branch.children, &mut self.root.children);
+                branch.subtree_word_count = self.root.subtree_word_count;
+                (branch, &chars[..])
+            }
+            Some((last, path)) => {
+                let parent: String = path.iter().collect();
+                let Some(Some(branch)) = self
+                    .find_node_mut(&parent)
+                    .and_then(|nd| nd.children.remove(last))
+                else {
+                    return 0;
+                };
+                self.prune_dangling(path);
+                (branch, path)
+            }
+        };
+
+        let removed = branch.get_subtree_word_count();
+        self.fix_path(path, removed);
+        let mut stack: Vec<&MwtNode<T>> = vec![&branch];
+        while let Some(nd) = stack.pop() {
+            if nd.get_end() {
+                self.add_order.remove(&nd.queued_at);
+            }
+            stack.extend(nd.children_iter().map(|(_, child)| child));
+        }
+        self.word_count -= removed;
+        removed
+    }
+
+    /// Drops the node at the end of `chars` if it neither holds nor leads
+    /// to a word any more, along with every ancestor that only led to it. The
+    /// root is never dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `chars` (`&[char]`) - Path from the root to the node that may be dangling
+    fn prune_dangling(&mut self, chars: &[char]) {
+        let mut path: Vec<&MwtNode<T>> = vec![&self.root];
+        for &ch in chars {
+            match path[path.len() - 1].get_child(ch) {
+                Some(nd) => path.push(nd),
+                None => return,
+            }
+        }
+        if chars.is_empty() || !path[chars.len()].is_dangling() {
+            return;
+        }
+
+        // Walk up to the deepest ancestor that still holds or leads to something else
+        let mut cut = chars.len() - 1;
+        while cut > 0 && !path[cut].get_end() && path[cut].child_count() == 1 {
+            cut -= 1;
+        }
+        let parent: String = chars[..cut].iter().collect();
+        if let Some(nd) = self.find_node_mut(&parent) {
+            nd.children.remove(&chars[cut]);
+        }
+    }
+
+    /// Updates the nodes along `chars` after `removed` words below the end
+    /// of it went away, taking them off each node's word count and rebuilding
+    /// each rank bound. The update stops early where the path does.
+    ///
+    /// # Arguments
+    ///
+    /// * `chars` (`&[char]`) - Path from the root along which words were removed
+    /// * `removed` (`usize`) - Number of words removed
+    fn fix_path(&mut self, chars: &[char], removed: usize) {
+        let mut path: Vec<&MwtNode<T>> = vec![&self.root];
+        for &ch in chars {
+            match path[path.len() - 1].get_child(ch) {
+                Some(nd) => path.push(nd),
+                None => break,
+            }
+        }
+
+        // Work the bounds out deepest first, so each node sees the new bound
+        // of its child on the path rather than the stale one
+        let mut bounds = vec![0; path.len()];
+        let mut below: Option<(char, i32)> = None;
+        for depth in (0..path.len()).rev() {
+            let bound = path[depth]
+                .children_iter()
+                .map(|(ch, child)| match below {
+                    Some((path_ch, bound)) if path_ch == ch => bound,
+                    _ => child.max_subtree_rank,
+                })
+                .fold(path[depth].rank, i32::max);
+            bounds[depth] = bound;
+            if depth > 0 {
+                below = Some((chars[depth - 1], bound));
+            }
+        }
+
+        let mut tmp: &mut MwtNode<T> = &mut self.root;
+        for (depth, bound) in bounds.into_iter().enumerate() {
+            if depth > 0 {
+                tmp = match tmp.get_child_mut(chars[depth - 1]) {
+                    Some(nd) => nd,
+                    None => panic!("Unreachable code hit: path node went missing!"),
+                };
+            }
+            tmp.max_subtree_rank = bound;
+            tmp.subtree_word_count -= removed;
+        }
+    }
+
+    /// Checks whether a string could be stored at all, i.e. it is not empty
+    /// and not over `max_word_len`. Over-long words would cost a node per
+    /// character, so they are refused before walking.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` (`&str`) - Word about to be inserted
+    ///
+    /// # Return value
+    ///
+    /// `true` if the word may be inserted, space permitting.
+    fn accepts(&self, data: &str) -> bool {
+        !data.is_empty()
+            && self
+                .max_word_len
+                .is_none_or(|max| data.chars().nth(max).is_none())
+    }
+
+    /// Finds the word to evict under `EvictionPolicy::LeastRecentlyUsed`.
+    ///
+    /// Words used since they were filed in the eviction order are filed
+    /// again under their last use as they come up, so the first word found
+    /// unused since it was filed is the least recently used one.
+    ///
+    /// # Return value
+    ///
+    /// The least recently used word, already taken out of the eviction order,
+    /// or `None` if no words are stored.
+    fn least_recently_used(&mut self) -> Option<String> {
+        while let Some((queued_at, word)) = self.add_order.pop_first() {
+            let nd = self.find_node_mut(&word)?;
+            let used = *nd.last_used.get_mut();
+            if used == queued_at {
+                return Some(word);
+            }
+            nd.queued_at = used;
+            self.add_order.insert(used, word);
+        }
+        None
+    }
+
+    /// Shared insertion logic for the `add_record` family.
+    ///
+    /// Iterates through the string to insert, creating
+    /// new `MwtNode`s as needed until the entire string is traversed,
+    /// then inserts the word at that node, advances the clock and stamps the
+    /// node with it.
+    ///
+    /// Once the word's rank is final, the path is walked a second time to
+    /// raise the `max_subtree_rank` bound of every node along it, and to
+    /// count a new word in each node's `subtree_word_count`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` (`String`) - New word to insert
+    /// * `decay` (`Option<f64>`) - If set, the node's `recency` is also updated with this factor
+    /// * `update` (`F: FnOnce(&mut MwtNode)`) - Applied to the word's node after the
+    ///   usual increment, for callers that change the rank by some other amount or
+    ///   record more about the word
+    ///
+    /// # Return value
+    ///
+    /// The node now holding the word, or `None` if the word was empty, too
+    /// long, or rejected because the MWT is full.
+    fn insert_record<F: FnOnce(&mut MwtNode<T>)>(
+        &mut self,
+        data: String,
+        decay: Option<f64>,
+        update: F,
+    ) -> Option<&mut MwtNode<T>> {
+        if !self.accepts(&data) {
+            return None;
+        }
+
+        // When full, a word that isn't stored yet either gets rejected or makes
+        // room by evicting the oldest word. Check before walking so no dangling
+        // nodes get created for a rejected word.
+        if self.max_words.is_some_and(|max| self.word_count >= max)
+            && !self.find_node(&data).is_some_and(|nd| nd.get_end())
+        {
+            match self.eviction {
+                EvictionPolicy::RejectNew => return None,
+                EvictionPolicy::LeastRecentlyUsed => match self.least_recently_used() {
+                    Some(victim) => {
+                        self.remove_record(&victim);
+                    }
+                    None => return None,
+                },
+            }
+        }
+
+        self.clock += 1;
+        let now = self.clock;
+        let track_order = self.eviction == EvictionPolicy::LeastRecentlyUsed;
+        let order_key = if track_order {
+            data.clone()
+        } else {
+            String::new()
+        };
+        let mut tmp = &mut self.root;
+
+        // Traverse MWT character by character
+        for ch in data.chars() {
+            let children = &mut tmp.children;
+            // If the value isn't present, add it to the map
+            tmp = match children
+                .entry(ch)
+                .or_insert_with(|| Some(Box::new(MwtNode::new())))
+            {
+                Some(nd) => nd,
+                None => panic!("Unreachable code hit: existing child had non-existing node!"),
+            }
+        }
+
+        // Insert the new word at the end
+        let is_new = !tmp.get_end();
+        if is_new {
+            tmp.toggle_end();
+            self.word_count += 1;
+        } else if track_order {
+            self.add_order.remove(&tmp.queued_at);
+        }
+        tmp.increment_rank(); // Increase number of times we've seen this word
+        update(tmp);
+        let rank = tmp.rank;
+
+        match decay {
+            Some(d) => tmp.bump_recency(d, now),
+            None => tmp.set_last_seen(now),
+        }
+        if track_order {
+            let used = self.use_clock.get_mut();
+            *used += 1;
+            tmp.last_used = AtomicU64::new(*used);
+            tmp.queued_at = *used;
+            self.add_order.insert(*used, order_key);
+        }
+
+        // Walk the path again now the rank is known, raising each bound
+        let added = usize::from(is_new);
+        let mut tmp: &mut MwtNode<T> = &mut self.root;
+        tmp.raise_max_rank(rank);
+        tmp.subtree_word_count += added;
+        for ch in data.chars() {
+            tmp = match tmp.children.get_mut(&ch).and_then(|c| c.as_deref_mut()) {
+                Some(nd) => nd,
+                None => panic!("Unreachable code hit: path was just created but is missing!"),
+            };
+            tmp.raise_max_rank(rank);
+            tmp.subtree_word_count += added;
+        }
+        #[cfg(feature = "store-words")]
+        if is_new && self.store_words {
+            tmp.set_data(data);
+        }
+        Some(tmp)
+    }
+}
+
+impl<T: Clone> Clone for Mwt<T> {
+    fn clone(&self) -> Mwt<T> {
+        Mwt {
+            root: self.root.clone(),
+            clock: self.clock,
+            word_count: self.word_count,
+            max_words: self.max_words,
+            eviction: self.eviction,
+            use_clock: AtomicU64::new(self.use_clock.load(Ordering::Relaxed)),
+            add_order: self.add_order.clone(),
+            max_word_len: self.max_word_len,
+            store_words: self.store_words,
+        }
+    }
+}
+
+/// Two MWTs are equal when they store the same words with the same ranks,
+/// regardless of the order the words were inserted in.
+impl<T> PartialEq for Mwt<T> {
+    fn eq(&self, other: &Mwt<T>) -> bool {
+        self.sorted_records() == other.sorted_records()
+    }
+}
+
+/// Owning iterator over the words of an MWT, created by `into_iter`.
+///
+/// Nodes are taken apart as the walk reaches them, so each stored word is
+/// moved out rather than cloned and memory is freed as iteration goes. When
+/// words aren't stored, they are rebuilt from the path instead. Words come
+/// out in no particular order.
+///
+/// # Fields
+///
+/// `stack` (`Vec<(usize, Option<char>, MwtNode)>`) - Nodes still to be visited, each with the
+/// length of its parent's path and the character leading to it.
+///
+/// `path` (`String`) - Characters leading from the root to the last node visited.
+pub struct IntoIter<T = ()> {
+    stack: Vec<(usize, Option<char>, MwtNode<T>)>,
+    path: String,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = (String, i32);
+
+    fn next(&mut self) -> Option<(String, i32)> {
+        while let Some((len, ch, mut nd)) = self.stack.pop() {
+            self.path.truncate(len);
+            self.path.extend(ch);
+            let len = self.path.len();
+            self.stack.extend(
+                nd.children
+                    .drain()
+                    .filter_map(|(ch, child)| child.map(|c| (len, Some(ch), *c))),
+            );
+            if nd.get_end() {
+                let word = match nd.take_data() {
+                    data if data.is_empty() => self.path.clone(),
+                    data => data,
+                };
+                return Some((word, nd.rank));
+            }
+        }
+        None
+    }
+}
+
+impl<T> IntoIterator for Mwt<T> {
+    type Item = (String, i32);
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter {
+            stack: vec![(0, None, *self.root)],
+            path: String::new(),
+        }
+    }
+}
+
+/// Mutable access to the root of an MWT, created by
+/// `Autocompleter::get_root_mut`.
+///
+/// Dereferences to the root `MwtNode`, whose `get_child_mut`,
+/// `children_iter_mut` and `set_rank` allow changing ranks in place. The
+/// shape of the MWT, i.e. which words are stored, can't be changed this way,
+/// so the cached word counts stay correct. Rank bounds are recomputed when
+/// the guard is dropped, which walks the whole MWT.
+///
+/// # Fields
+///
+/// `trie` (`&mut Mwt`) - MWT being modified.
+pub struct RootMut<'a, T = ()> {
+    trie: &'a mut Mwt<T>,
+}
+
+impl<'a, T> RootMut<'a, T> {
+    /// Constructs a new guard over an MWT.
+    ///
+    /// # Arguments
+    ///
+    /// * `trie` (`&mut Mwt`) - MWT to give access to
+    pub fn new(trie: &'a mut Mwt<T>) -> RootMut<'a, T> {
+        RootMut { trie }
+    }
+}
+
+impl<T> Deref for RootMut<'_, T> {
+    type Target = MwtNode<T>;
+
+    fn deref(&self) -> &MwtNode<T> {
+        &self.trie.root
+    }
+}
+
+impl<T> DerefMut for RootMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut MwtNode<T> {
+        &mut self.trie.root
+    }
+}
+
+/// Refreshes the rank bounds, since ranks may have been raised in place.
+impl<T> Drop for RootMut<'_, T> {
+    fn drop(&mut self) {
+        self.trie.recompute_bounds();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Deep enough that any walk recursing once per level overflows a test thread's stack
+    const DEPTH: usize = 200_000;
+
+    #[test]
+    fn deep_chain_survives_every_walk_and_drop() {
+        let deep = "a".repeat(DEPTH);
+        let mut trie: Mwt = Mwt::new();
+        trie.add_record(deep.clone());
+        trie.add_record(deep[..DEPTH / 2].to_string());
+        trie.add_record("ab".to_string());
+
+        let copy = trie.clone();
+        assert!(copy == trie);
+        drop(copy);
+        trie.validate().unwrap();
+        trie.compact();
+        trie.recompute_bounds();
+        assert_eq!(trie.node_count(), DEPTH + 2);
+        assert_eq!(trie.subtree(&deep[..10]).map(|sub| sub.len()), Some(2));
+
+        assert!(trie.remove_record(&deep));
+        trie.validate().unwrap();
+        assert_eq!(trie.node_count(), DEPTH / 2 + 2);
+        trie.add_record(deep.clone());
+        assert_eq!(trie.retain(|word, _| word.len() < DEPTH), 1);
+        trie.validate().unwrap();
+        trie.add_record(deep);
+        assert_eq!(trie.remove_prefix("aa"), 2);
+        trie.validate().unwrap();
+        assert_eq!(trie.len(), 1);
+        assert_eq!(trie.into_iter().count(), 1);
     }
 }
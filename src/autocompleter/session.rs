@@ -0,0 +1,153 @@
+use super::mwt::MwtNode;
+use super::{Autocompleter, ELEMENTS_TO_RETURN};
+
+/// Cursor for completing a word as it's typed, one character at a time.
+///
+/// The session remembers the MWT node the typed prefix leads to, so each
+/// keystroke only descends one level instead of walking down from the root
+/// again. Created with `Autocompleter::session`.
+///
+/// # Fields
+///
+/// `ac` (`&Autocompleter`) - Completer the session is reading from.
+///
+/// `prefix` (`String`) - Everything typed so far.
+///
+/// `path` (`Vec<&MwtNode>`) - Nodes from the root down to the end of the typed prefix, as far
+/// as it matches stored words.
+///
+/// `key` (`String`) - Characters leading from the root to the last node of `path`.
+///
+/// `pushed` (`Vec<usize>`) - Number of folded characters each typed character added to the walk,
+/// so `pop_char` can undo it.
+///
+/// `missed` (`usize`) - Number of characters typed since the prefix stopped matching.
+pub struct Session<'a, T = ()> {
+    ac: &'a Autocompleter<T>,
+    prefix: String,
+    path: Vec<&'a MwtNode<T>>,
+    key: String,
+    pushed: Vec<usize>,
+    missed: usize,
+}
+
+impl<'a, T> Session<'a, T> {
+    /// Constructs a new session with an empty prefix.
+    ///
+    /// # Arguments
+    ///
+    /// `ac` (`&Autocompleter`) - Completer to read from.
+    pub(super) fn new(ac: &'a Autocompleter<T>) -> Session<'a, T> {
+        Session {
+            ac,
+            prefix: String::new(),
+            path: vec![ac.trie.get_root()],
+            key: String::new(),
+            pushed: Vec::new(),
+            missed: 0,
+        }
+    }
+
+    /// Types another character at the end of the prefix.
+    ///
+    /// # Arguments
+    ///
+    /// `ch` (`char`) - Character typed.
+    ///
+    /// # Return value
+    ///
+    /// `true` if some stored word still begins with the prefix.
+    pub fn push_char(&mut self, ch: char) -> bool {
+        // A run of whitespace collapses to one space, so only its first
+        // character takes a step
+        let repeated_space = self.ac.settings.normalize_whitespace
+            && ch.is_whitespace()
+            && self.prefix.ends_with(char::is_whitespace);
+        self.prefix.push(ch);
+        if self.missed > 0 {
+            self.missed += 1;
+            return false;
+        }
+        if repeated_space {
+            self.pushed.push(0);
+            return true;
+        }
+
+        // Follow the character the same way a full prefix would be folded
+        let mut buf = [0; 4];
+        let key = self.ac.fold(ch.encode_utf8(&mut buf)).into_owned();
+        let mut steps = 0;
+        for key_ch in key.chars() {
+            match self.current().get_child(key_ch) {
+                Some(child) => {
+                    self.path.push(child);
+                    self.key.push(key_ch);
+                    steps += 1;
+                }
+                None => {
+                    // Undo any partial descent so the path stays in step with `pushed`
+                    self.path.truncate(self.path.len() - steps);
+                    for _ in 0..steps {
+                        self.key.pop();
+                    }
+                    self.missed = 1;
+                    return false;
+                }
+            }
+        }
+        self.pushed.push(steps);
+        true
+    }
+
+    /// Deletes the last character of the prefix, like a backspace.
+    ///
+    /// # Return value
+    ///
+    /// The removed character, or `None` if the prefix was already empty.
+    pub fn pop_char(&mut self) -> Option<char> {
+        let ch = self.prefix.pop()?;
+        if self.missed > 0 {
+            self.missed -= 1;
+        } else if let Some(steps) = self.pushed.pop() {
+            self.path.truncate(self.path.len() - steps);
+            for _ in 0..steps {
+                self.key.pop();
+            }
+        }
+        Some(ch)
+    }
+
+    /// Accessor method for everything typed so far.
+    ///
+    /// # Return value
+    ///
+    /// The current prefix.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Gets the completions for the current prefix, exactly as
+    /// `predict_completions` would, without walking down to it again.
+    ///
+    /// # Return value
+    ///
+    /// Up to 10 completions, in the configured `SortOrder`.
+    #[must_use]
+    pub fn completions(&self) -> Vec<String> {
+        if self.missed > 0 || !self.ac.long_enough(&self.prefix) {
+            return Vec::new();
+        }
+        let mut dfs_results = self.ac.candidates_below(self.current(), &self.key);
+        self.ac.sort_results(&mut dfs_results);
+        dfs_results
+            .into_iter()
+            .take(ELEMENTS_TO_RETURN)
+            .map(|r| self.ac.surface(r.data))
+            .collect()
+    }
+
+    /// Node the matched part of the prefix leads to.
+    fn current(&self) -> &'a MwtNode<T> {
+        self.path[self.path.len() - 1]
+    }
+}
@@ -0,0 +1,146 @@
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use super::Autocompleter;
+
+/// Wrapper for sharing one `Autocompleter` between threads, such as a server
+/// that answers predictions while also learning new words.
+///
+/// Predictions take a read lock, so any number of them run at once, while
+/// adding a word takes the write lock. Wrap it in an `Arc` to hand it to
+/// several threads. A thread that panics while holding the lock doesn't
+/// stop the others from using it afterwards.
+///
+/// A plain `Autocompleter` has no locking at all, which is still the better
+/// choice when only one thread uses it.
+///
+/// # Fields
+///
+/// `inner` (`RwLock<Autocompleter<T>>`) - The shared completer.
+pub struct SharedAutocompleter<T = ()> {
+    inner: RwLock<Autocompleter<T>>,
+}
+
+impl<T> SharedAutocompleter<T> {
+    /// Constructs a new `SharedAutocompleter` around an existing completer.
+    ///
+    /// # Arguments
+    ///
+    /// `ac` (`Autocompleter`) - Completer to share.
+    pub fn new(ac: Autocompleter<T>) -> SharedAutocompleter<T> {
+        SharedAutocompleter {
+            inner: RwLock::new(ac),
+        }
+    }
+
+    /// Runs `Autocompleter::predict_completions` under a read lock.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+    ///
+    /// # Return value
+    ///
+    /// The same completions as `Autocompleter::predict_completions`.
+    #[must_use]
+    pub fn predict(&self, prefix: &str) -> Vec<String> {
+        self.read().predict_completions(prefix)
+    }
+
+    /// Runs `Autocompleter::add_word` under the write lock.
+    ///
+    /// # Arguments
+    ///
+    /// `word` (`String`) - Word to add to the structure.
+    pub fn add(&self, word: String) {
+        self.write().add_word(word);
+    }
+
+    /// Takes a read lock, for any other query. Adding words blocks until the
+    /// guard is dropped.
+    ///
+    /// # Return value
+    ///
+    /// A guard giving shared access to the completer.
+    pub fn read(&self) -> RwLockReadGuard<'_, Autocompleter<T>> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Takes the write lock, for any other change. Every other access blocks
+    /// until the guard is dropped.
+    ///
+    /// # Return value
+    ///
+    /// A guard giving exclusive access to the completer.
+    pub fn write(&self) -> RwLockWriteGuard<'_, Autocompleter<T>> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Consumes the wrapper, giving back the completer.
+    ///
+    /// # Return value
+    ///
+    /// The shared `Autocompleter`.
+    pub fn into_inner(self) -> Autocompleter<T> {
+        self.inner
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T> From<Autocompleter<T>> for SharedAutocompleter<T> {
+    fn from(ac: Autocompleter<T>) -> SharedAutocompleter<T> {
+        SharedAutocompleter::new(ac)
+    }
+}
+
+impl Default for SharedAutocompleter {
+    fn default() -> SharedAutocompleter {
+        SharedAutocompleter::new(Autocompleter::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn readers_and_writers_share_one_completer() {
+        let shared = Arc::new(SharedAutocompleter::default());
+        shared.add("seed".to_string());
+
+        let writers: Vec<_> = (0..4)
+            .map(|w| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        shared.add(format!("s{w}-{i}"));
+                    }
+                })
+            })
+            .collect();
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        // Every prediction sees a consistent completer, never a half-added word
+                        let completions = shared.predict("s");
+                        assert!(!completions.is_empty());
+                        assert!(completions.len() <= crate::ELEMENTS_TO_RETURN);
+                        shared.read().validate().unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in writers.into_iter().chain(readers) {
+            handle.join().unwrap();
+        }
+
+        let ac = Arc::into_inner(shared).unwrap().into_inner();
+        assert_eq!(ac.len(), 401);
+        assert_eq!(ac.prefix_count("s3-"), 100);
+        ac.validate().unwrap();
+    }
+}
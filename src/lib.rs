@@ -0,0 +1,42 @@
+//! Word autocompletion backed by a multi-way trie (MWT).
+//!
+//! The main entry point is `Autocompleter`, which can be filled from a dictionary
+//! file or word by word and then queried for completions of a prefix.
+//!
+//! For custom traversals, `Autocompleter::get_root` exposes the trie itself as
+//! a tree of read-only `MwtNode`s.
+//!
+//! To share one completer between threads, wrap it in a `SharedAutocompleter`.
+//!
+//! For a one-off query, `complete` builds a completer from some text and
+//! completes a single prefix.
+
+mod autocompleter;
+pub use autocompleter::{
+    Autocompleter, AutocompleterBuilder, EvictionPolicy, IntoIter, MwtNode, PredictError,
+    PredictionResult, RootMut, ScoredWord, Session, SharedAutocompleter, SortOrder, Stats,
+    TieBreak, ELEMENTS_TO_RETURN,
+};
+
+/// Completes a prefix against a piece of text in one call, for quick scripts
+/// that don't want to manage an `Autocompleter`. The text is split into words
+/// the same way a dictionary file is.
+///
+/// This builds a new `Autocompleter` with `Autocompleter::from_text` on every
+/// call and throws it away afterwards, so it costs time proportional to the
+/// whole corpus each time. To complete more than a single prefix, build an
+/// `Autocompleter` once and query it instead.
+///
+/// # Arguments
+///
+/// `corpus` (`&str`) - Text to take the words from.
+///
+/// `prefix` (`&str`) - Word to search for, either complete or the beginning.
+///
+/// # Return value
+///
+/// The same completions as `Autocompleter::predict_completions`.
+#[must_use]
+pub fn complete(corpus: &str, prefix: &str) -> Vec<String> {
+    Autocompleter::from_text(corpus).predict_completions(prefix)
+}
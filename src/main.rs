@@ -1,118 +1,531 @@
+use std::cell::RefCell;
 use std::env;
-use std::io::{stdin, stdout, Write};
-use std::process::{exit, ExitCode};
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::rc::Rc;
 
-mod autocompleter;
-use autocompleter::Autocompleter;
+use rustyline::completion::Completer;
+use rustyline::config::{Behavior, Config as EditorConfig};
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::{Context, Editor, Helper, Highlighter, Hinter, Validator};
 
-// Maximum number of command line arguments expected
-const MAX_ARG_NUM: usize = 2;
+use rustocompleter::{Autocompleter, ELEMENTS_TO_RETURN};
 
-// Filename index
-const FILE_IDX: usize = 1;
+// Maximum number of positional (non-flag) command line arguments expected
+const MAX_ARG_NUM: usize = 1;
+
+// Dictionary filename meaning "read the dictionary from standard input"
+const STDIN_ARG: &str = "-";
+
+// Flag used to switch prediction output to JSON
+const JSON_FLAG: &str = "--json";
+
+// Flag used to predict every prefix read from stdin, printing JSON lines
+const JSONL_FLAG: &str = "--jsonl";
+
+// Flag used to include each completion's count in JSON output
+const COUNTS_FLAG: &str = "--counts";
+
+// Environment variable that sets the starting number of completions shown
+const LIMIT_VAR: &str = "RUSTOCOMPLETER_LIMIT";
+
+// Name of the file in the user's home directory that stores input history
+const HISTORY_FILE: &str = ".rustocompleter_history";
 
 // Prompt string used in the main program loop
-const PROMPT: &str = "Enter a command ((p)redict completions, (a)dd word, (q)uit): ";
+const PROMPT: &str =
+    "Enter a command ((p)redict completions, (f)uzzy predict, (a)dd word, (n)umber of completions, (i)nfo, (tree) dump, (r)eload, (q)uit): ";
 
 /// Small struct only used for parsing command line arguments.
 struct Config {
     filename: String,
+    json: bool,
+    jsonl: bool,
+    counts: bool,
+    limit: usize,
 }
 
 impl Config {
     /// Constructs a new Config object.
     ///
     /// Provides arg parsing and returns a Result of either the constructed object
-    /// or an error string.
+    /// or an error string. The completion limit comes from `RUSTOCOMPLETER_LIMIT`,
+    /// see `parse_limit`.
     fn new(args: &[String]) -> Result<Config, &str> {
-        if args.len() > MAX_ARG_NUM {
+        let mut json = false;
+        let mut jsonl = false;
+        let mut counts = false;
+        let mut positional: Vec<&String> = Vec::new();
+
+        // Skip the program name, then pull flags out from the positional args.
+        for arg in args.iter().skip(1) {
+            match arg.as_str() {
+                JSON_FLAG => json = true,
+                JSONL_FLAG => jsonl = true,
+                COUNTS_FLAG => counts = true,
+                _ => positional.push(arg),
+            }
+        }
+
+        if positional.len() > MAX_ARG_NUM {
             return Err("number of arguments passed in was incorrect.");
         }
+        if jsonl && positional.first().is_some_and(|f| f.as_str() == STDIN_ARG) {
+            return Err(
+                "--jsonl reads prefixes from stdin, so the dictionary can't come from stdin too.",
+            );
+        }
+
+        let limit = parse_limit(env::var(LIMIT_VAR).ok().as_deref());
+
+        Ok(Config {
+            filename: positional
+                .first()
+                .map_or_else(String::new, |f| f.to_string()),
+            json,
+            jsonl,
+            counts,
+            limit,
+        })
+    }
+}
+
+/// Parses the value of `RUSTOCOMPLETER_LIMIT` into a completion limit,
+/// ignoring surrounding whitespace. Falls back to the library default when
+/// the variable is unset, zero or not a number.
+fn parse_limit(value: Option<&str>) -> usize {
+    value
+        .and_then(|l| l.trim().parse::<usize>().ok())
+        .filter(|&limit| limit > 0)
+        .unwrap_or(ELEMENTS_TO_RETURN)
+}
+
+/// Escapes a string so it can be embedded inside a JSON string literal.
+fn json_escape(st: &str) -> String {
+    let mut out = String::with_capacity(st.len());
+    for ch in st.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Formats the completions for a prefix as a single JSON object of the form
+/// `{"prefix": "...", "completions": ["...", ...]}`.
+fn completions_to_json(prefix: &str, completions: &[String]) -> String {
+    let words: Vec<String> = completions
+        .iter()
+        .map(|w| format!("\"{}\"", json_escape(w)))
+        .collect();
+    json_object(prefix, &words)
+}
+
+/// Formats the completions for a prefix, with their counts, as a single JSON
+/// object of the form
+/// `{"prefix": "...", "completions": [{"word": "...", "count": 1}, ...]}`.
+fn counted_completions_to_json(prefix: &str, completions: &[(String, i32)]) -> String {
+    let words: Vec<String> = completions
+        .iter()
+        .map(|(w, count)| format!("{{\"word\": \"{}\", \"count\": {count}}}", json_escape(w)))
+        .collect();
+    json_object(prefix, &words)
+}
+
+/// Wraps completions already formatted as JSON values into the object
+/// printed for a prefix.
+fn json_object(prefix: &str, completions: &[String]) -> String {
+    format!(
+        "{{\"prefix\": \"{}\", \"completions\": [{}]}}",
+        json_escape(prefix),
+        completions.join(", ")
+    )
+}
+
+/// Predicts the completions for a prefix and formats them as JSON, with
+/// their counts when `counts` is set.
+fn predict_json(ac: &Autocompleter, prefix: &str, limit: usize, counts: bool) -> String {
+    if counts {
+        counted_completions_to_json(prefix, &ac.predict_completions_with_counts(prefix, limit))
+    } else {
+        completions_to_json(prefix, &ac.predict_completions_n(prefix, limit))
+    }
+}
+
+/// Non-interactive mode used with `--jsonl`. Reads one prefix per line from
+/// stdin and prints its completions as a JSON object on a line of its own,
+/// flushing after each one so results can be consumed as they're produced.
+///
+/// Returns an error string if reading input or writing output fails.
+fn run_jsonl(ac: &Autocompleter, limit: usize, counts: bool) -> Result<(), String> {
+    let mut out = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|e| format!("Error occurred reading input from stdin: {e}"))?;
+        let prefix = line.trim();
+        writeln!(out, "{}", predict_json(ac, prefix, limit, counts))
+            .and_then(|()| out.flush())
+            .map_err(|e| format!("Error writing completions to stdout: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Line editor helper that offers completions from the dictionary when Tab is pressed.
+///
+/// Completion is only offered while `active` is set, so that command prompts
+/// don't suggest dictionary words.
+#[derive(Helper, Highlighter, Hinter, Validator)]
+struct PredictHelper {
+    ac: Rc<RefCell<Autocompleter>>,
+    active: bool,
+}
+
+impl Completer for PredictHelper {
+    type Candidate = String;
 
-        if args.len() == 1 {
-            Ok(Config {
-                filename: String::new(),
-            })
-        } else {
-            Ok(Config {
-                filename: args[FILE_IDX].clone(),
-            })
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        if !self.active {
+            return Ok((pos, Vec::new()));
         }
+        // The whole line is the prefix, so completions replace it from the start.
+        Ok((0, self.ac.borrow().predict_completions(&line[..pos])))
     }
 }
 
+/// Line editor type used for all user input.
+type LineEditor = Editor<PredictHelper, DefaultHistory>;
+
 /// Function used to grab user input from the command line.
-/// Prints out the given prompt first before grabbing.
-fn grab_input(prompt: &str) -> String {
-    // Print the prompt out first.
-    print!("{prompt}");
-    if let Err(e) = stdout().flush() {
-        eprintln!("Error flushing output stream: {e}");
-        exit(1);
+/// Prints out the given prompt first before grabbing. If `complete` is set,
+/// pressing Tab cycles through the top completions for what's been typed.
+///
+/// Returns the trimmed input, `None` once input runs out (Ctrl-D or the end of
+/// piped input), or an error string if it couldn't be read.
+fn grab_input(rl: &mut LineEditor, prompt: &str, complete: bool) -> Result<Option<String>, String> {
+    if let Some(helper) = rl.helper_mut() {
+        helper.active = complete;
     }
 
     // Grab the user's input string, and strip trailing characters.
-    let mut option = String::new();
-    if let Err(e) = stdin().read_line(&mut option) {
-        eprintln!("Error occurred reading input from stdin: {e}");
-        exit(1);
-    };
-    String::from(option.trim())
+    match rl.readline(prompt) {
+        Ok(line) => {
+            let line = line.trim();
+            // Remember the input so it can be recalled with the arrow keys.
+            if !line.is_empty() {
+                let _ = rl.add_history_entry(line);
+            }
+            Ok(Some(String::from(line)))
+        }
+        Err(ReadlineError::Eof) => {
+            // Finish the prompt's line so the shell prompt starts on a fresh one.
+            println!();
+            Ok(None)
+        }
+        Err(e) => Err(format!("Error occurred reading input from stdin: {e}")),
+    }
+}
+
+/// Location of the history file, if the user's home directory is known.
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+/// Main program loop. Reads and carries out commands until the user quits.
+///
+/// Running out of input is treated like the `q` command. Returns an error
+/// string if reading input fails.
+fn run(rl: &mut LineEditor, ac: &Rc<RefCell<Autocompleter>>, conf: &Config) -> Result<(), String> {
+    // Number of completions shown by the `p` command, adjustable with `n`.
+    let mut limit = conf.limit;
+
+    loop {
+        let Some(input) = grab_input(rl, PROMPT, false)? else {
+            return Ok(());
+        };
+
+        match input.as_str() {
+            "a" => {
+                // Add a word to the dictionary
+                let Some(st) = grab_input(rl, "Enter string to add to completer: ", false)? else {
+                    return Ok(());
+                };
+                ac.borrow_mut().add_word(st);
+                println!("String added!");
+            }
+            "p" => {
+                // Do a prediction search.
+                let Some(prefix) = grab_input(rl, "Enter prefix to get completions for: ", true)?
+                else {
+                    return Ok(());
+                };
+                if conf.json {
+                    println!(
+                        "{}",
+                        predict_json(&ac.borrow(), &prefix, limit, conf.counts)
+                    );
+                } else {
+                    let result = ac.borrow().predict_completions_n(&prefix, limit);
+                    println!(
+                        "Completions for {prefix} (most to least popular): {:?}",
+                        result
+                    );
+                }
+            }
+            "f" => {
+                // Do a prediction search that tolerates typos in the prefix.
+                let Some(prefix) = grab_input(rl, "Enter prefix to get completions for: ", true)?
+                else {
+                    return Ok(());
+                };
+                let distance = loop {
+                    let Some(num) = grab_input(rl, "Enter maximum edit distance: ", false)? else {
+                        return Ok(());
+                    };
+                    match num.parse::<usize>() {
+                        Ok(d) => break d,
+                        Err(e) => println!("`{num}` is not a valid distance ({e}), try again"),
+                    }
+                };
+                let result = ac.borrow().predict_completions_fuzzy(&prefix, distance);
+                if conf.json {
+                    println!("{}", completions_to_json(&prefix, &result));
+                } else {
+                    println!(
+                        "Completions for {prefix} within {distance} edits (closest first): {:?}",
+                        result
+                    );
+                }
+            }
+            "n" => {
+                // Change how many completions are shown.
+                let Some(num) = grab_input(rl, "Enter number of completions to show: ", false)?
+                else {
+                    return Ok(());
+                };
+                match num.parse::<usize>() {
+                    Ok(n) => {
+                        limit = n;
+                        println!("Now showing up to {limit} completions!");
+                    }
+                    Err(e) => println!("`{num}` is not a valid number ({e}), keeping {limit}"),
+                }
+            }
+            "i" => {
+                // Print some figures about the loaded dictionary.
+                let stats = ac.borrow().stats();
+                println!("Distinct words: {}", stats.words);
+                println!("Total occurrences: {}", stats.occurrences);
+                println!("Trie nodes: {}", stats.nodes);
+                println!("Longest word length: {}", stats.max_word_len);
+            }
+            "tree" => {
+                // Print the part of the trie under a prefix, for debugging.
+                let Some(prefix) = grab_input(rl, "Enter prefix to show (blank for all): ", true)?
+                else {
+                    return Ok(());
+                };
+                match ac.borrow().subtree(&prefix) {
+                    Some(sub) => print!("{}", sub.dump_tree()),
+                    None => println!("No words begin with `{prefix}`"),
+                }
+            }
+            "r" => {
+                // Re-read the dictionary file given at startup.
+                if conf.filename.is_empty() {
+                    println!(
+                        "No dictionary file was given at startup, so there is nothing to reload."
+                    );
+                    continue;
+                }
+                if conf.filename == STDIN_ARG {
+                    println!(
+                        "The dictionary was read from standard input, which can't be read again."
+                    );
+                    continue;
+                }
+                // Load into a fresh completer first, so a missing or broken
+                // file leaves the current dictionary in place.
+                match Autocompleter::from_file(&conf.filename) {
+                    Ok(acc) => {
+                        *ac.borrow_mut() = acc;
+                        println!("Reloaded `{}`!", conf.filename);
+                    }
+                    Err(e) => eprintln!("{e} (keeping the current dictionary)"),
+                }
+            }
+            "q" => break,
+            _ => println!("Command {input} is not valid"),
+        }
+    }
+    Ok(())
 }
 
 /// Main program driver. Parses command line args, instantiates an autocompleter,
 /// and starts the main program loop.
 fn main() -> ExitCode {
-    println!();
-
     // Grab the command line arguments to start.
     let args: Vec<String> = env::args().collect();
     let conf = match Config::new(&args) {
         Ok(c) => c,
         Err(e) => {
             eprintln!("Error parsing command line arguments: {e}");
-            eprintln!("USAGE: {} path/to/dictionary/file (optional)", args[0]);
+            eprintln!(
+                "USAGE: {} [{JSON_FLAG} | {JSONL_FLAG}] [{COUNTS_FLAG}] path/to/dictionary/file (optional, `{STDIN_ARG}` for stdin)",
+                args[0]
+            );
             return ExitCode::FAILURE;
         }
     };
+    // Keep stdout to just the results when they're meant for another program.
+    if !conf.jsonl {
+        println!();
+    }
 
     // Instantiate an autocompleter.
-    // If no arg is provided, start a blank one. Else, parse the file and load it in.
-    let mut ac = if conf.filename.as_str() == "" {
+    // If no arg is provided, start a blank one. A `-` reads the dictionary from
+    // stdin. Else, parse the file and load it in.
+    let from_stdin = conf.filename == STDIN_ARG;
+    let ac = if conf.filename.as_str() == "" {
         Autocompleter::new()
+    } else if from_stdin {
+        match Autocompleter::from_reader(io::stdin().lock()) {
+            Ok(acc) => acc,
+            Err(e) => {
+                eprintln!("Error reading dictionary from standard input: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
     } else {
         match Autocompleter::from_file(&conf.filename) {
             Ok(acc) => acc,
             Err(e) => {
                 eprintln!("{e}");
-                exit(1);
+                return ExitCode::FAILURE;
             }
         }
     };
 
-    loop {
-        let input = grab_input(PROMPT);
-
-        match input.as_str() {
-            "a" => {
-                // Add a word to the dictionary
-                let st = grab_input("Enter string to add to completer: ");
-                ac.add_word(st);
-                println!("String added!");
-            }
-            "p" => {
-                // Do a prediction search.
-                let prefix = grab_input("Enter prefix to get completions for: ");
-                let result = ac.predict_completions(&prefix);
-                println!(
-                    "Completions for {prefix} (most to least popular): {:?}",
-                    result
-                );
+    if conf.jsonl {
+        return match run_jsonl(&ac, conf.limit, conf.counts) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("{e}");
+                ExitCode::FAILURE
             }
-            "q" => break,
-            _ => println!("Command {input} is not valid"),
+        };
+    }
+
+    // Share the autocompleter with the line editor so Tab can offer completions.
+    let ac = Rc::new(RefCell::new(ac));
+    // Stdin has been used up by the dictionary, so commands have to come from
+    // the terminal instead.
+    let behavior = if from_stdin {
+        Behavior::PreferTerm
+    } else {
+        Behavior::Stdio
+    };
+    let editor_conf = EditorConfig::builder().behavior(behavior).build();
+    let mut rl = match LineEditor::with_config(editor_conf) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Error setting up line editor: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    rl.set_helper(Some(PredictHelper {
+        ac: Rc::clone(&ac),
+        active: false,
+    }));
+
+    // Pick up history from earlier sessions. A missing file just means there is none yet.
+    let history = history_path();
+    if let Some(path) = &history {
+        let _ = rl.load_history(path);
+    }
+
+    let result = run(&mut rl, &ac, &conf);
+
+    if let Some(path) = &history {
+        if let Err(e) = rl.save_history(path) {
+            eprintln!("Error saving input history to `{}`: {e}", path.display());
         }
     }
 
-    ExitCode::SUCCESS
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limit_falls_back_to_the_default() {
+        assert_eq!(parse_limit(Some("3")), 3);
+        assert_eq!(parse_limit(Some(" 12\n")), 12);
+        // Unset, zero or not a number all give the default of 10
+        assert_eq!(parse_limit(None), 10);
+        assert_eq!(parse_limit(Some("0")), 10);
+        assert_eq!(parse_limit(Some("")), 10);
+        assert_eq!(parse_limit(Some("lots")), 10);
+        assert_eq!(parse_limit(Some("-5")), 10);
+    }
+
+    #[test]
+    fn config_pulls_flags_out_of_the_arguments() {
+        let args = ["prog", "--jsonl", "dict.txt", "--counts"].map(String::from);
+        let conf = Config::new(&args).unwrap();
+        assert_eq!(conf.filename, "dict.txt");
+        assert!(conf.jsonl && conf.counts && !conf.json);
+
+        let args = ["prog", "a.txt", "b.txt"].map(String::from);
+        assert!(Config::new(&args).is_err());
+        let args = ["prog", "--jsonl", "-"].map(String::from);
+        assert!(Config::new(&args).is_err());
+    }
+
+    #[test]
+    fn json_escape_handles_special_characters() {
+        assert_eq!(json_escape("plain"), "plain");
+        assert_eq!(json_escape(r#"say "hi""#), r#"say \"hi\""#);
+        assert_eq!(json_escape(r"back\slash"), r"back\\slash");
+        assert_eq!(json_escape("a\nb\rc\td"), r"a\nb\rc\td");
+        assert_eq!(json_escape("\u{0}\u{1b}"), r"\u0000\u001b");
+        // Characters outside the control range are left alone
+        assert_eq!(json_escape("café ☕"), "café ☕");
+    }
+
+    #[test]
+    fn completions_are_plain_strings_unless_counted() {
+        let words = ["a\"b".to_string(), "c".to_string()];
+        assert_eq!(
+            completions_to_json("p", &words),
+            r#"{"prefix": "p", "completions": ["a\"b", "c"]}"#
+        );
+        assert_eq!(
+            counted_completions_to_json("p", &[("c".to_string(), 2)]),
+            r#"{"prefix": "p", "completions": [{"word": "c", "count": 2}]}"#
+        );
+        assert_eq!(
+            completions_to_json("", &[]),
+            r#"{"prefix": "", "completions": []}"#
+        );
+    }
 }
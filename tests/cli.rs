@@ -0,0 +1,112 @@
+//! End-to-end tests of the `rustocompleter` binary, driven through stdin.
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+// Longest a run may take before it counts as hanging
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Writes `contents` to a scratch dictionary file, returning its path.
+fn dictionary(name: &str, contents: &str) -> String {
+    let file = format!("rustocompleter-cli-{}-{name}", std::process::id());
+    let path = std::env::temp_dir().join(file);
+    std::fs::write(&path, contents).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+/// Runs the binary with `args` and environment `vars`, piping `input` to its
+/// stdin, and waits for it to exit. Panics if it hasn't exited within
+/// `TIMEOUT`, e.g. because it keeps looping once input runs out.
+fn run(args: &[&str], vars: &[(&str, &str)], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rustocompleter"))
+        .args(args)
+        .env_remove("RUSTOCOMPLETER_LIMIT")
+        .envs(vars.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || tx.send(child.wait_with_output().unwrap()));
+    rx.recv_timeout(TIMEOUT)
+        .expect("the binary didn't exit once its input ran out")
+}
+
+#[test]
+fn limit_comes_from_the_environment() {
+    let words: Vec<String> = (0..15).map(|i| format!("word{i:02}")).collect();
+    let dict = dictionary("limit.txt", &words.join("\n"));
+    let count = |vars: &[(&str, &str)]| {
+        let output = run(&["--jsonl", "--counts", &dict], vars, "word\n");
+        assert!(output.status.success());
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .matches("\"word\": ")
+            .count()
+    };
+
+    assert_eq!(count(&[("RUSTOCOMPLETER_LIMIT", "3")]), 3);
+    assert_eq!(count(&[("RUSTOCOMPLETER_LIMIT", " 12 ")]), 12);
+    // Unset, zero or not a number falls back to the default of 10
+    assert_eq!(count(&[]), 10);
+    assert_eq!(count(&[("RUSTOCOMPLETER_LIMIT", "0")]), 10);
+    assert_eq!(count(&[("RUSTOCOMPLETER_LIMIT", "lots")]), 10);
+    std::fs::remove_file(&dict).unwrap();
+}
+
+#[test]
+fn empty_stdin_exits_cleanly() {
+    let output = run(&[], &[], "");
+    assert!(output.status.success());
+    assert!(!String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("is not valid"));
+
+    // Running out of input partway through a command quits too
+    let output = run(&[], &[], "p\n");
+    assert!(output.status.success());
+}
+
+#[test]
+fn jsonl_prints_one_line_per_prefix() {
+    let dict = dictionary("jsonl.txt", "apple apple apply\nbanana\n");
+    let output = run(&["--jsonl", &dict], &[], "app\nzzz\n  ban  \n");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            r#"{"prefix": "app", "completions": ["apple", "apply"]}"#,
+            r#"{"prefix": "zzz", "completions": []}"#,
+            r#"{"prefix": "ban", "completions": ["banana"]}"#,
+        ]
+    );
+
+    // With `--counts`, each completion comes with its count
+    let output = run(&["--jsonl", "--counts", &dict], &[], "app\nzzz\n  ban  \n");
+    std::fs::remove_file(&dict).unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            r#"{"prefix": "app", "completions": [{"word": "apple", "count": 2}, {"word": "apply", "count": 1}]}"#,
+            r#"{"prefix": "zzz", "completions": []}"#,
+            r#"{"prefix": "ban", "completions": [{"word": "banana", "count": 1}]}"#,
+        ]
+    );
+}